@@ -2,25 +2,244 @@ use ffmpeg_next::{
     channel_layout::ChannelLayout, codec::Context, format::Sample, format::input,
     format::sample::Type::Planar, software, util::frame::audio::Audio,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::str::FromStr;
 
+use crate::i18n::trf;
+use crate::progress::Reporter;
+
+/// 输入文件中一条音频流的信息
+#[derive(Debug, Clone)]
+pub struct AudioStreamInfo {
+    pub index: usize,
+    pub codec: String,
+    pub language: Option<String>,
+    /// ffmpeg 没有这条流的解码器时为 `None`
+    pub channels: Option<u16>,
+    pub title: Option<String>,
+    /// 是不是 ffmpeg 认为的"最佳"音频流
+    pub best: bool,
+}
+
+impl fmt::Display for AudioStreamInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.channels {
+            Some(channels) => write!(f, "#{} {} {}ch", self.index, self.codec, channels)?,
+            None => write!(f, "#{} {} ?ch", self.index, self.codec)?,
+        }
+        if let Some(language) = &self.language {
+            write!(f, " [{}]", language)?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " {}", title)?;
+        }
+        Ok(())
+    }
+}
+
+/// 列出输入文件中所有的音频流。个别流不能解码时照样列出，只有一条都不能解码时才失败
+pub fn list_audio_streams(input_path: &Path) -> Result<Vec<AudioStreamInfo>, Box<dyn Error>> {
+    let ictx = input(input_path)?;
+    let best_index = ictx
+        .streams()
+        .best(ffmpeg_next::media::Type::Audio)
+        .map(|stream| stream.index());
+
+    let mut streams = Vec::new();
+    for stream in ictx.streams() {
+        let parameters = stream.parameters();
+        if parameters.medium() != ffmpeg_next::media::Type::Audio {
+            continue;
+        }
+        let channels = Context::from_parameters(parameters.clone())
+            .and_then(|context| context.decoder().audio())
+            .map(|decoder| decoder.channels())
+            .inspect_err(|e| warn!("Cannot decode audio stream #{}: {}", stream.index(), e))
+            .ok();
+        let metadata = stream.metadata();
+        streams.push(AudioStreamInfo {
+            index: stream.index(),
+            codec: parameters.id().name().to_string(),
+            language: metadata.get("language").map(str::to_string),
+            channels,
+            title: metadata.get("title").map(str::to_string),
+            best: Some(stream.index()) == best_index,
+        });
+    }
+    if !streams.is_empty() && streams.iter().all(|stream| stream.channels.is_none()) {
+        return Err("None of the audio streams can be decoded".into());
+    }
+    Ok(streams)
+}
+
+/// 多声道输入的处理方式，配置文件里和命令行一样写成字符串
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum ChannelMode {
+    /// 混缩成单声道
+    #[default]
+    Downmix,
+    /// 只取其中一个声道(从 0 开始)
+    Pick(u16),
+    /// 每个声道单独输出
+    Split,
+}
+
+impl fmt::Display for ChannelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelMode::Downmix => write!(f, "downmix"),
+            ChannelMode::Pick(channel) => write!(f, "{}", channel + 1),
+            ChannelMode::Split => write!(f, "split"),
+        }
+    }
+}
+
+/// 接受 `downmix`、`split` 或者从 1 开始的声道序号
+impl FromStr for ChannelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "downmix" => Ok(ChannelMode::Downmix),
+            "split" => Ok(ChannelMode::Split),
+            other => match other.parse::<u16>() {
+                Ok(channel) if channel > 0 => Ok(ChannelMode::Pick(channel - 1)),
+                _ => Err(format!(
+                    "invalid channel mode: {} (expected downmix, split or a channel number)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl From<ChannelMode> for String {
+    fn from(mode: ChannelMode) -> Self {
+        mode.to_string()
+    }
+}
+
+impl TryFrom<String> for ChannelMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// 只处理输入文件的一段，`None` 表示从头开始/一直到结尾
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeRange {
+    pub from: Option<f64>,
+    pub to: Option<f64>,
+}
+
+impl TimeRange {
+    /// 从两个时间字符串解析，空字符串表示不限制
+    pub fn parse(from: &str, to: &str) -> Result<Self, String> {
+        let parse = |s: &str| {
+            let s = s.trim();
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                parse_timestamp(s).map(Some)
+            }
+        };
+        let range = Self {
+            from: parse(from)?,
+            to: parse(to)?,
+        };
+        range.validate()?;
+        Ok(range)
+    }
+
+    /// 任务文件和命令行合并出的范围也要经过这里检查
+    pub fn validate(&self) -> Result<(), String> {
+        for time in [self.from, self.to].into_iter().flatten() {
+            if !time.is_finite() || time < 0.0 {
+                return Err(trf("time_out_of_range", &[&time]));
+            }
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && from >= to
+        {
+            return Err(trf("range_end_before_start", &[&to, &from]));
+        }
+        Ok(())
+    }
+
+    /// 输出的第一个采样在原文件中的时间(秒)
+    pub fn start(&self) -> f64 {
+        self.from.unwrap_or(0.0)
+    }
+}
+
+/// 解析 `90`、`12:30`、`1:02:03.5` 这样的时间，返回秒数
+pub fn parse_timestamp(s: &str) -> Result<f64, String> {
+    let invalid = || trf("time_invalid", &[&s]);
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let mut seconds = 0.0;
+    for (idx, part) in parts.iter().enumerate() {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(invalid());
+        }
+        // 第一段之后的分、秒必须小于 60，只有最后一段可以有小数
+        let last = idx == parts.len() - 1;
+        if (idx > 0 && value >= 60.0) || (!last && value.fract() != 0.0) {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// `stream_index` 为 `None` 时使用 ffmpeg 选出的最佳音频流。
+/// 返回值每个元素是一路单声道音频，只有 `ChannelMode::Split` 时会有多路
 pub fn do_resample(
-    progress: Arc<Mutex<f32>>,
+    reporter: &mut Reporter<'_>,
     target_sample_rate: u32,
     input_path: &Path,
-) -> Result<Vec<f32>, Box<dyn Error>> {
+    stream_index: Option<usize>,
+    channel_mode: ChannelMode,
+    range: TimeRange,
+) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
     // 打开输入文件
-    let mut ictx = input(input_path).unwrap();
+    let mut ictx = input(input_path)
+        .map_err(|e| format!("Cannot open {}: {}", input_path.to_string_lossy(), e))?;
 
     // 查找音频流
-    let stream = ictx
-        .streams()
-        .best(ffmpeg_next::media::Type::Audio)
-        .unwrap();
+    let stream = match stream_index {
+        Some(index) => ictx
+            .stream(index)
+            .filter(|stream| stream.parameters().medium() == ffmpeg_next::media::Type::Audio)
+            .ok_or(format!("Stream #{} is not an audio stream", index))?,
+        None => ictx
+            .streams()
+            .best(ffmpeg_next::media::Type::Audio)
+            .ok_or("No audio stream found")?,
+    };
     let audio_stream_index = stream.index();
+    let time_base = f64::from(stream.time_base());
+    // 流的时长未知时退而使用整个文件的时长
+    let stream_duration = if stream.duration() > 0 {
+        Some(stream.duration() as f64 * time_base)
+    } else if ictx.duration() > 0 {
+        Some(ictx.duration() as f64 * f64::from(ffmpeg_next::rescale::TIME_BASE))
+    } else {
+        None
+    };
+    info!("audio stream: #{}", audio_stream_index);
+    info!("duration: {:?}s", stream_duration);
 
     let context = Context::from_parameters(stream.parameters())?;
     let mut decoder = context.decoder().audio()?;
@@ -29,6 +248,9 @@ pub fn do_resample(
     let original_sample_rate = decoder.rate();
     let original_format = decoder.format();
     let original_channels = decoder.channels();
+    if original_channels == 0 {
+        return Err("The audio stream has no channels".into());
+    }
     //fill in default Channel layout if it's empty
     if decoder.channel_layout().is_empty() {
         decoder.set_channel_layout(ChannelLayout::default(original_channels as i32));
@@ -38,6 +260,27 @@ pub fn do_resample(
     info!("original_channel_layout: {:?}", original_channel_layout);
     info!("channels: {}", original_channels);
     info!("original_sample_rate: {}", original_sample_rate);
+    info!("channel_mode: {:?}", channel_mode);
+
+    // 要从重采样结果里取出的声道(planar 格式下每个声道一个 plane)
+    let (output_channel_layout, planes): (ChannelLayout, Vec<usize>) = match channel_mode {
+        ChannelMode::Downmix => (ChannelLayout::MONO, vec![0]),
+        ChannelMode::Pick(channel) if channel < original_channels => {
+            (original_channel_layout, vec![channel as usize])
+        }
+        ChannelMode::Pick(channel) => {
+            return Err(format!(
+                "Channel {} does not exist, the stream only has {} channels",
+                channel + 1,
+                original_channels
+            )
+            .into());
+        }
+        ChannelMode::Split => (
+            original_channel_layout,
+            (0..original_channels as usize).collect(),
+        ),
+    };
 
     // 创建重采样器
     let mut resampler = software::resampling::Context::get(
@@ -45,35 +288,122 @@ pub fn do_resample(
         original_channel_layout,
         original_sample_rate,
         Sample::I16(Planar),
-        ChannelLayout::MONO,
+        output_channel_layout,
         target_sample_rate,
     )
-    .unwrap();
+    .map_err(|e| format!("Cannot create resampler: {}", e))?;
+
+    if let Some(from) = range.from {
+        // 跳到 from 之前最近的关键帧，多解出来的部分下面再丢掉
+        let ts = (from / f64::from(ffmpeg_next::rescale::TIME_BASE)) as i64;
+        ictx.seek(ts, ..ts)?;
+        info!("seek to {}s", from);
+    }
+
+    // 第一个输出采样在原文件中的时间，以及已经输出过的采样数
+    let mut first_time: Option<f64> = None;
+    let mut emitted: u64 = 0;
+    let mut reached_end = false;
 
-    let mut output_samples: Vec<f32> = Vec::new();
-    let packets: Vec<_> = ictx.packets().collect();
-    let total=packets.len();
+    // 按解码出的时间计算进度，需要处理的总时长
+    let total_seconds = range
+        .to
+        .or(stream_duration)
+        .map(|end| end - range.start())
+        .filter(|total| *total > 0.0);
+
+    let mut output_samples: Vec<Vec<f32>> = vec![Vec::new(); planes.len()];
     // 读取并处理每一帧
-    for (idx,(stream, packet)) in packets.iter().enumerate() {
-        {        *progress.lock().unwrap() = idx as f32 / total as f32;}
+    for (stream, packet) in ictx.packets() {
+        if reached_end {
+            break;
+        }
         if stream.index() != audio_stream_index {
             continue;
         }
 
-        decoder.send_packet(packet).unwrap();
+        decoder
+            .send_packet(&packet)
+            .map_err(|e| format!("Cannot decode audio packet: {}", e))?;
 
         let mut decoded: Audio = Audio::empty();
         while decoder.receive_frame(&mut decoded).is_ok() {
+            let frame_time = decoded.pts().map(|pts| pts as f64 * time_base);
+            if let (Some(frame_time), Some(total_seconds)) = (frame_time, total_seconds) {
+                reporter.set_seconds(frame_time - range.start(), total_seconds);
+            }
+            let start_time = *first_time.get_or_insert(frame_time.unwrap_or(range.start()));
             let mut resampled = Audio::empty();
             resampler.run(&decoded, &mut resampled)?;
 
-            for sample in resampled.plane::<i16>(0) {
-                let f32_sample = *sample as f32 / i16::MAX as f32;
-                output_samples.push(f32_sample);
+            // 只保留落在 range 里的采样
+            let frame_samples = resampled.samples();
+            let sample_time =
+                |k: usize| start_time + (emitted + k as u64) as f64 / target_sample_rate as f64;
+            let keep_from = (0..frame_samples)
+                .find(|k| sample_time(*k) >= range.start())
+                .unwrap_or(frame_samples);
+            let end = range
+                .to
+                .and_then(|to| (keep_from..frame_samples).find(|k| sample_time(*k) >= to));
+            let keep_to = match end {
+                Some(k) => {
+                    reached_end = true;
+                    k
+                }
+                None => frame_samples,
+            };
+            emitted += frame_samples as u64;
+
+            for (output, plane) in output_samples.iter_mut().zip(&planes) {
+                for sample in &resampled.plane::<i16>(*plane)[keep_from..keep_to] {
+                    let f32_sample = *sample as f32 / i16::MAX as f32;
+                    output.push(f32_sample);
+                }
+            }
+            if reached_end {
+                break;
             }
         }
     }
 
-    debug!("Output samples count: {}", output_samples.len());
+    debug!(
+        "Output samples count: {}",
+        output_samples.first().map_or(0, Vec::len)
+    );
     Ok(output_samples)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("90"), Ok(90.0));
+        assert_eq!(parse_timestamp("12:30"), Ok(750.0));
+        assert_eq!(parse_timestamp("1:02:03.5"), Ok(3723.5));
+        assert_eq!(parse_timestamp(" 0:59.9 "), Ok(59.9));
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        for s in [
+            "", "abc", "inf", "NaN", "-5", "1:75", "1:60", "1:02:60", "1.5:00", "1:2:3:4", "1::2",
+        ] {
+            assert!(parse_timestamp(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(TimeRange::parse("", ""), Ok(TimeRange::default()));
+        assert_eq!(
+            TimeRange::parse("1:00", "1:30"),
+            Ok(TimeRange { from: Some(60.0), to: Some(90.0) })
+        );
+        assert!(TimeRange::parse("1:30", "1:00").is_err());
+        assert!(TimeRange::parse("10", "10").is_err());
+        assert!(TimeRange { from: Some(f64::INFINITY), to: None }.validate().is_err());
+    }
+}
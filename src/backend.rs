@@ -0,0 +1,33 @@
+use log::info;
+
+/// 编译时启用的 whisper 推理后端，CPU 总是可用
+pub fn whisper_backends() -> Vec<&'static str> {
+    let mut backends = vec!["CPU"];
+    if cfg!(feature = "vulkan") {
+        backends.push("Vulkan");
+    }
+    if cfg!(feature = "cuda") {
+        backends.push("CUDA");
+    }
+    if cfg!(feature = "metal") {
+        backends.push("Metal");
+    }
+    if cfg!(feature = "openblas") {
+        backends.push("OpenBLAS");
+    }
+    backends
+}
+
+/// 编译时启用的 SileroVAD(onnxruntime) 执行后端
+pub fn vad_backends() -> Vec<&'static str> {
+    let mut backends = vec!["CPU"];
+    if cfg!(feature = "directml") {
+        backends.push("DirectML");
+    }
+    backends
+}
+
+pub fn log_backends() {
+    info!("Whisper backends: {}", whisper_backends().join(", "));
+    info!("VAD backends: {}", vad_backends().join(", "));
+}
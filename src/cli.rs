@@ -0,0 +1,274 @@
+use crate::pipeline::{self, Job};
+use crate::progress::JobEvent;
+use crate::{audio, models, profile, segments, vad};
+use clap::{ArgAction, Parser};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+/// WhisperGuiNeo 命令行模式，不带参数启动时打开图形界面
+#[derive(Parser, Debug)]
+#[command(name = "WhisperGuiNeo", version)]
+pub struct Cli {
+    /// 输入的音频/视频文件
+    #[arg(required_unless_present = "job")]
+    pub input: Option<PathBuf>,
+
+    /// 从 TOML/JSON 任务文件读取全部参数，例如转录时保存在字幕旁边的 *.job.toml；
+    /// 同时给出的 --profile 和其它参数优先
+    #[arg(long)]
+    pub job: Option<PathBuf>,
+
+    /// 列出输入文件中的所有音频流后退出
+    #[arg(long)]
+    pub list_streams: bool,
+
+    /// 要转录的音频流序号，可以用逗号分隔多个，每条流单独输出一个字幕文件
+    #[arg(short, long = "stream", value_delimiter = ',')]
+    pub streams: Vec<usize>,
+
+    /// 声道处理方式: downmix(混缩，默认)、split(每个声道分别转录) 或者声道序号(从 1 开始)
+    #[arg(long = "channels")]
+    pub channel_mode: Option<audio::ChannelMode>,
+
+    /// split 模式下每个声道的说话人标签，用逗号分隔
+    #[arg(long, value_delimiter = ',')]
+    pub speakers: Vec<String>,
+
+    /// 从这个时间开始转录，格式为 [[hh:]mm:]ss
+    #[arg(long, value_parser = audio::parse_timestamp)]
+    pub from: Option<f64>,
+
+    /// 转录到这个时间为止，格式为 [[hh:]mm:]ss
+    #[arg(long, value_parser = audio::parse_timestamp)]
+    pub to: Option<f64>,
+
+    /// 使用 profiles/<名字>.toml 中保存的设置，也可以是配置文件的路径；命令行上给出的参数优先
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// 只做 VAD，按这个格式导出语音段: json、csv 或 audacity，不需要 whisper 模型
+    #[arg(long)]
+    pub segments: Option<segments::SegmentFormat>,
+
+    /// 配合 --segments，把每个语音段另存为 WAV 文件
+    #[arg(long, requires = "segments")]
+    pub segment_wav: bool,
+
+    /// Whisper 模型 (ggml-*.bin)
+    #[arg(short, long)]
+    pub model: Option<PathBuf>,
+
+    /// VAD 后端: silero(默认) 或 energy
+    #[arg(long)]
+    pub vad: Option<vad::VadBackend>,
+
+    /// SileroVAD 模型 (silero_vad.onnx)
+    #[arg(long)]
+    pub vad_model: Option<PathBuf>,
+
+    /// SileroVAD 模型运行的采样率: 16000(默认) 或 8000，只影响送进模型的数据
+    #[arg(long)]
+    pub vad_sample_rate: Option<u32>,
+
+    /// 语音概率高于这个值的帧视为语音，默认 0.35
+    #[arg(long)]
+    pub vad_threshold: Option<f32>,
+
+    /// 静音持续多少秒才结束一个语音段，默认 0.2
+    #[arg(long)]
+    pub min_silence: Option<f32>,
+
+    /// 默认 zh
+    #[arg(short, long)]
+    pub language: Option<String>,
+
+    #[arg(long)]
+    pub prompt: Option<String>,
+
+    /// whisper 使用的线程数，默认 8
+    #[arg(long)]
+    pub threads: Option<u32>,
+
+    /// 大于 0 时使用 beam search，默认 0(贪心解码)
+    #[arg(long)]
+    pub beam_size: Option<u32>,
+
+    /// 翻译成英文
+    #[arg(long, action = ArgAction::SetTrue, overrides_with = "no_translate")]
+    pub translate: bool,
+
+    /// 不翻译，覆盖任务文件或配置里打开的翻译
+    #[arg(long, action = ArgAction::SetTrue, overrides_with = "translate")]
+    pub no_translate: bool,
+
+    /// 输出的字幕文件，默认与输入文件同名
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// 有命令行参数时走命令行模式
+pub fn requested() -> bool {
+    std::env::args_os().len() > 1
+}
+
+/// 发布版是 GUI 子系统的程序，没有自己的控制台。从 cmd/PowerShell 启动时连接到父进程的控制台，
+/// 否则命令行模式的输出都看不到
+#[cfg(windows)]
+pub fn attach_console() {
+    use windows_sys::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+    // 从资源管理器启动时没有父控制台，失败了也没关系
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn attach_console() {}
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    if cli.list_streams {
+        let input = cli.input.ok_or("--list-streams needs an input file")?;
+        for stream in audio::list_audio_streams(&input)? {
+            println!("{}{}", stream, if stream.best { " (default)" } else { "" });
+        }
+        return Ok(());
+    }
+
+    let job = cli.into_job()?;
+    if job.mode == pipeline::JobMode::Transcribe
+        && let Some(warning) =
+            models::WhisperModelInfo::inspect(Path::new(&job.whisper_path)).language_warning(&job.language)
+    {
+        eprintln!("warning: {}", warning);
+    }
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        // Box<dyn Error> 不能跨线程传递，错误本身会通过 JobEvent::Error 送回来
+        let _ = pipeline::run_job(&job, &sender);
+    });
+    let mut result = Ok(());
+    for event in receiver {
+        match event {
+            JobEvent::Progress(progress) => {
+                eprint!("\r{:<8} {}", format!("{:?}", progress.stage), progress.summary());
+            }
+            JobEvent::Warning(message) => eprintln!("\nwarning: {}", message),
+            JobEvent::Error(message) => result = Err(message.into()),
+            JobEvent::Finished { outputs, summary } => {
+                eprintln!("\r{:<8} {}", "Finished", summary.summary());
+                for output in outputs {
+                    println!("{}", output.to_string_lossy());
+                }
+            }
+            JobEvent::Stage(_) | JobEvent::Subtitle(_) | JobEvent::Audio(_) => {}
+        }
+    }
+    worker.join().map_err(|_| "transcription thread panicked")?;
+    result
+}
+
+impl Cli {
+    /// --translate/--no-translate 都没给时为 `None`，同时给出时后面的优先
+    fn translate(&self) -> Option<bool> {
+        match (self.translate, self.no_translate) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// 依次合并任务文件、配置文件和命令行参数，得到最终的任务
+    pub fn into_job(self) -> Result<Job, Box<dyn Error>> {
+        // 下面会逐个移走字段，先取出来
+        let translate = self.translate();
+        let mut job = match &self.job {
+            Some(path) => Job::load(path)?,
+            None => Job::default(),
+        };
+        if let Some(name) = &self.profile {
+            profile::load_file_or_name(name)?.apply_to(&mut job);
+        }
+        if let Some(input) = self.input {
+            job.audio_path = input;
+        }
+        if !self.streams.is_empty() {
+            job.streams = self.streams;
+        }
+        if let Some(channel_mode) = self.channel_mode {
+            job.channel_mode = channel_mode;
+        }
+        if !self.speakers.is_empty() {
+            job.speakers = self.speakers;
+        }
+        if self.from.is_some() {
+            job.range.from = self.from;
+        }
+        if self.to.is_some() {
+            job.range.to = self.to;
+        }
+        if let Some(format) = self.segments {
+            job.mode = pipeline::JobMode::Segments(segments::SegmentExport {
+                format,
+                write_wav: self.segment_wav,
+            });
+        }
+        if let Some(model) = self.model {
+            job.whisper_path = model.to_string_lossy().into_owned();
+        }
+        if let Some(backend) = self.vad {
+            job.vad_backend = backend;
+        }
+        if let Some(vad_model) = self.vad_model {
+            job.vad_path = vad_model.to_string_lossy().into_owned();
+        }
+        if let Some(sample_rate) = self.vad_sample_rate {
+            job.vad_sample_rate = sample_rate;
+        }
+        if let Some(threshold) = self.vad_threshold {
+            job.vad_settings.threshold = threshold;
+        }
+        if let Some(min_silence) = self.min_silence {
+            job.vad_settings.min_silence = min_silence;
+        }
+        if let Some(language) = self.language {
+            job.language = language;
+        }
+        if let Some(prompt) = self.prompt {
+            job.initial_prompt = prompt;
+        }
+        if let Some(threads) = self.threads {
+            job.whisper_settings.threads = threads;
+        }
+        if let Some(beam_size) = self.beam_size {
+            job.whisper_settings.beam_size = beam_size;
+        }
+        if let Some(translate) = translate {
+            job.whisper_settings.translate = translate;
+        }
+        if let Some(output) = self.output {
+            job.output_path = output;
+        }
+
+        if job.audio_path.as_os_str().is_empty() {
+            return Err("an input file is required".into());
+        }
+        job.range
+            .validate()
+            .map_err(|e| format!("--from/--to: {}", e))?;
+        if job.mode == pipeline::JobMode::Transcribe && job.whisper_path.is_empty() {
+            return Err("--model is required".into());
+        }
+        if job.vad_backend.needs_model() && job.vad_path.is_empty() {
+            return Err("--vad-model is required for the silero backend".into());
+        }
+        // 只做 VAD 时扩展名会在 Job::outputs 里换成对应格式的
+        if job.output_path.as_os_str().is_empty() {
+            job.output_path = job.audio_path.with_extension("srt");
+        }
+        Ok(job)
+    }
+}
@@ -0,0 +1,355 @@
+use eframe::egui;
+use eframe::egui::{DragValue, TextEdit};
+use log::info;
+#[cfg(feature = "playback")]
+use log::warn;
+use srtlib::{Subtitle, Subtitles, Timestamp};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::audio::parse_timestamp;
+use crate::i18n::{tr, trf};
+#[cfg(feature = "playback")]
+use crate::playback::Player;
+use crate::progress::{AudioEvent, format_millis};
+
+/// 字幕编辑器中的一行，时间是相对于原文件开头的毫秒数
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+}
+
+enum Action {
+    #[cfg(feature = "playback")]
+    Play(usize),
+    #[cfg(feature = "playback")]
+    Stop,
+    Split(usize),
+    MergeNext(usize),
+    Delete(usize),
+}
+
+/// 转录完成后编辑字幕，并回放对应的音频
+pub struct TranscriptEditor {
+    path: PathBuf,
+    rows: Vec<Row>,
+    /// 这个输出文件对应的音频，分声道时每个声道一路
+    #[cfg(feature = "playback")]
+    tracks: Vec<AudioEvent>,
+    #[cfg(feature = "playback")]
+    player: Option<Player>,
+    playing: Option<usize>,
+    /// 整体平移的毫秒数
+    shift_ms: i64,
+    dirty: bool,
+    status: Option<String>,
+}
+
+impl TranscriptEditor {
+    #[cfg_attr(not(feature = "playback"), allow(unused_variables))]
+    pub fn open(path: &Path, tracks: Vec<AudioEvent>) -> Result<Self, Box<dyn Error>> {
+        let subs = Subtitles::parse_from_file(path, None)?;
+        let rows = subs
+            .to_vec()
+            .into_iter()
+            .map(|sub| Row {
+                start_ms: timestamp_millis(&sub.start_time),
+                end_ms: timestamp_millis(&sub.end_time),
+                text: sub.text,
+            })
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            rows,
+            #[cfg(feature = "playback")]
+            tracks,
+            #[cfg(feature = "playback")]
+            player: None,
+            playing: None,
+            shift_ms: 0,
+            dirty: false,
+            status: None,
+        })
+    }
+
+    /// 按开始时间排序、重新编号后写回原来的输出文件
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.rows.sort_by_key(|row| row.start_ms);
+        let subs: Vec<Subtitle> = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| {
+                Subtitle::new(
+                    idx + 1,
+                    Timestamp::from_milliseconds(row.start_ms.max(0) as u32),
+                    Timestamp::from_milliseconds(row.end_ms.max(row.start_ms).max(0) as u32),
+                    row.text.clone(),
+                )
+            })
+            .collect();
+        Subtitles::new_from_vec(subs).write_to_file(&self.path, None)?;
+        info!("Saved to {}", self.path.to_string_lossy());
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// 在文字中间附近的标点或空格处拆成两行，时间按字数比例分配
+    pub fn split(&mut self, idx: usize) {
+        let row = &self.rows[idx];
+        let chars: Vec<char> = row.text.chars().collect();
+        let at = split_point(&chars);
+        let split_ms = if chars.is_empty() {
+            (row.start_ms + row.end_ms) / 2
+        } else {
+            row.start_ms + (row.end_ms - row.start_ms) * at as i64 / chars.len() as i64
+        };
+        let second = Row {
+            start_ms: split_ms,
+            end_ms: row.end_ms,
+            text: chars[at..].iter().collect::<String>().trim().to_string(),
+        };
+        let first = &mut self.rows[idx];
+        first.end_ms = split_ms;
+        first.text = chars[..at].iter().collect::<String>().trim().to_string();
+        self.rows.insert(idx + 1, second);
+        self.dirty = true;
+    }
+
+    /// 把下一行合并到这一行
+    pub fn merge_next(&mut self, idx: usize) {
+        if idx + 1 >= self.rows.len() {
+            return;
+        }
+        let next = self.rows.remove(idx + 1);
+        let row = &mut self.rows[idx];
+        row.end_ms = row.end_ms.max(next.end_ms);
+        row.text = join_text(&row.text, &next.text);
+        self.dirty = true;
+    }
+
+    pub fn shift_all(&mut self, ms: i64) {
+        for row in &mut self.rows {
+            row.start_ms = (row.start_ms + ms).max(0);
+            row.end_ms = (row.end_ms + ms).max(0);
+        }
+        self.dirty = true;
+    }
+
+    /// 播放某一行对应的音频
+    #[cfg(feature = "playback")]
+    pub fn play(&mut self, idx: usize) -> Result<(), Box<dyn Error>> {
+        let row = &self.rows[idx];
+        let track = self.track_for(row).ok_or(tr("editor_no_audio"))?;
+        let to_index = |ms: i64| {
+            let seconds = ms as f64 / 1000.0 - track.start;
+            ((seconds * track.sample_rate as f64).max(0.0) as usize).min(track.samples.len())
+        };
+        let (from, to) = (to_index(row.start_ms), to_index(row.end_ms));
+        if from >= to {
+            return Err(tr("editor_out_of_range").into());
+        }
+        let sample_rate = track.sample_rate;
+        let samples = track.samples[from..to].to_vec();
+        if self.player.is_none() {
+            self.player = Some(Player::new()?);
+        }
+        if let Some(player) = &mut self.player {
+            player.play(sample_rate, samples)?;
+        }
+        self.playing = Some(idx);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        #[cfg(feature = "playback")]
+        if let Some(player) = &mut self.player {
+            player.stop();
+        }
+        self.playing = None;
+    }
+
+    /// 分声道的字幕以 `[标签]` 开头，用它找到对应声道，否则用第一路
+    #[cfg(feature = "playback")]
+    fn track_for(&self, row: &Row) -> Option<&AudioEvent> {
+        self.tracks
+            .iter()
+            .find(|track| {
+                track
+                    .label
+                    .as_ref()
+                    .is_some_and(|label| row.text.starts_with(&format!("[{}]", label)))
+            })
+            .or(self.tracks.first())
+    }
+
+    /// 返回 true 表示编辑器被关闭
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut close = false;
+        #[cfg(feature = "playback")]
+        if self.playing.is_some()
+            && !self.player.as_ref().is_some_and(|player| player.is_playing())
+        {
+            self.playing = None;
+        }
+        if self.playing.is_some() {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+
+        ui.horizontal(|ui| {
+            ui.strong(self.path.to_string_lossy());
+            if self.dirty {
+                ui.weak(tr("editor_unsaved"));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr("editor_shift"));
+            ui.add(DragValue::new(&mut self.shift_ms).speed(10).suffix(" ms"));
+            if ui
+                .add_enabled(self.shift_ms != 0, egui::Button::new(tr("editor_apply")))
+                .clicked()
+            {
+                self.shift_all(self.shift_ms);
+                self.shift_ms = 0;
+            }
+            ui.separator();
+            if ui.add_enabled(self.dirty, egui::Button::new(tr("editor_save"))).clicked() {
+                self.status = match self.save() {
+                    Ok(()) => Some(tr("editor_saved").to_string()),
+                    Err(e) => Some(trf("editor_save_failed", &[&e])),
+                };
+            }
+            let close_text = if self.dirty {
+                tr("editor_discard")
+            } else {
+                tr("editor_close")
+            };
+            if ui.button(close_text).clicked() {
+                self.stop();
+                close = true;
+            }
+            if let Some(status) = &self.status {
+                ui.label(status);
+            }
+        });
+        ui.separator();
+
+        let mut action = None;
+        let row_height = ui.spacing().interact_size.y;
+        let count = self.rows.len();
+        egui::ScrollArea::vertical()
+            .id_salt("transcript_editor")
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, count, |ui, range| {
+                for idx in range {
+                    #[cfg(feature = "playback")]
+                    let playing = self.playing == Some(idx);
+                    let row = &mut self.rows[idx];
+                    ui.horizontal(|ui| {
+                        #[cfg(feature = "playback")]
+                        if ui.button(if playing { "■" } else { "▶" }).clicked() {
+                            action = Some(if playing {
+                                Action::Stop
+                            } else {
+                                Action::Play(idx)
+                            });
+                        }
+                        let start = ui.add(time_drag(&mut row.start_ms));
+                        ui.label("->");
+                        let end = ui.add(time_drag(&mut row.end_ms));
+                        if start.changed() || end.changed() {
+                            row.end_ms = row.end_ms.max(row.start_ms);
+                            self.dirty = true;
+                        }
+                        if ui.small_button(tr("editor_split")).clicked() {
+                            action = Some(Action::Split(idx));
+                        }
+                        if ui
+                            .add_enabled(idx + 1 < count, egui::Button::new(tr("editor_merge")).small())
+                            .clicked()
+                        {
+                            action = Some(Action::MergeNext(idx));
+                        }
+                        if ui.small_button(tr("delete")).clicked() {
+                            action = Some(Action::Delete(idx));
+                        }
+                        let text = ui.add(
+                            TextEdit::singleline(&mut row.text)
+                                .desired_width(ui.available_width()),
+                        );
+                        if text.changed() {
+                            self.dirty = true;
+                        }
+                    });
+                }
+            });
+
+        match action {
+            #[cfg(feature = "playback")]
+            Some(Action::Play(idx)) => {
+                if let Err(e) = self.play(idx) {
+                    warn!("无法播放: {}", e);
+                    self.status = Some(trf("editor_play_failed", &[&e]));
+                }
+            }
+            #[cfg(feature = "playback")]
+            Some(Action::Stop) => self.stop(),
+            Some(Action::Split(idx)) => {
+                self.stop();
+                self.split(idx);
+            }
+            Some(Action::MergeNext(idx)) => {
+                self.stop();
+                self.merge_next(idx);
+            }
+            Some(Action::Delete(idx)) => {
+                self.stop();
+                self.rows.remove(idx);
+                self.dirty = true;
+            }
+            None => {}
+        }
+        close
+    }
+}
+
+/// 以 `hh:mm:ss.mmm` 显示和输入的毫秒数
+fn time_drag(ms: &mut i64) -> DragValue<'_> {
+    DragValue::new(ms)
+        .speed(10)
+        .range(0..=i64::MAX)
+        .custom_formatter(|value, _| format_millis(value as i64))
+        .custom_parser(|text| parse_timestamp(text).ok().map(|seconds| seconds * 1000.0))
+}
+
+fn timestamp_millis(timestamp: &Timestamp) -> i64 {
+    let (hours, minutes, seconds, millis) = timestamp.get();
+    ((hours as i64 * 60 + minutes as i64) * 60 + seconds as i64) * 1000 + millis as i64
+}
+
+/// 从中间向两边找最近的空格或标点，找不到就从正中间拆
+fn split_point(chars: &[char]) -> usize {
+    let middle = chars.len() / 2;
+    let is_break = |c: char| c.is_whitespace() || "，。、；！？,.;!?".contains(c);
+    (0..=middle)
+        .flat_map(|distance| [middle + distance, middle.wrapping_sub(distance)])
+        .find(|at| *at > 0 && *at < chars.len() && is_break(chars[*at - 1]))
+        .unwrap_or(middle)
+}
+
+/// 英文之间补一个空格，中文直接拼接
+fn join_text(first: &str, second: &str) -> String {
+    let needs_space = first
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c.is_ascii_punctuation())
+        && second.chars().next().is_some_and(|c| c.is_ascii_alphanumeric());
+    if needs_space {
+        format!("{} {}", first, second)
+    } else {
+        format!("{}{}", first, second)
+    }
+}
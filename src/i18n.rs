@@ -0,0 +1,121 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 界面文字的字符串表，键相同，缺少的键回退到中文
+const ZH_CN: &str = include_str!("../assets/i18n/zh-CN.toml");
+const EN: &str = include_str!("../assets/i18n/en.toml");
+
+/// 界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Locale {
+    #[default]
+    ZhCn,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::ZhCn, Locale::En];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh-CN",
+            Locale::En => "en",
+        }
+    }
+
+    /// 用这种语言本身写的名字，切换语言的下拉框里用
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Locale::ZhCn => "简体中文",
+            Locale::En => "English",
+        }
+    }
+
+    /// 按系统的区域设置选择，不是中文的都用英文
+    pub fn system() -> Self {
+        match sys_locale::get_locale() {
+            Some(locale) if locale.to_ascii_lowercase().starts_with("zh") => Locale::ZhCn,
+            Some(_) => Locale::En,
+            None => Locale::default(),
+        }
+    }
+
+    fn catalog(&self) -> &'static HashMap<String, String> {
+        static ZH_CN_CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+        static EN_CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+        match self {
+            Locale::ZhCn => ZH_CN_CATALOG.get_or_init(|| parse_catalog(self.id(), ZH_CN)),
+            Locale::En => EN_CATALOG.get_or_init(|| parse_catalog(self.id(), EN)),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl From<Locale> for String {
+    fn from(locale: Locale) -> Self {
+        locale.id().to_string()
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.id().eq_ignore_ascii_case(&s))
+            .ok_or_else(|| format!("unknown locale: {}", s))
+    }
+}
+
+fn parse_catalog(id: &str, text: &str) -> HashMap<String, String> {
+    toml::from_str(text).unwrap_or_else(|e| {
+        warn!("Invalid string table {}: {}", id, e);
+        HashMap::new()
+    })
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_locale(locale: Locale) {
+    CURRENT.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn locale() -> Locale {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Locale::En,
+        _ => Locale::ZhCn,
+    }
+}
+
+/// 当前语言下 `key` 对应的文字，两个表里都没有时返回 `key` 本身
+pub fn tr(key: &'static str) -> &'static str {
+    locale()
+        .catalog()
+        .get(key)
+        .or_else(|| Locale::ZhCn.catalog().get(key))
+        .map_or(key, String::as_str)
+}
+
+/// 和 `tr` 一样，再把文字里的 `{}` 依次替换成 `args`
+pub fn trf(key: &'static str, args: &[&dyn fmt::Display]) -> String {
+    let mut parts = tr(key).split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for (idx, part) in parts.enumerate() {
+        if let Some(arg) = args.get(idx) {
+            text += &arg.to_string();
+        }
+        text += part;
+    }
+    text
+}
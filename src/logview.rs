@@ -0,0 +1,183 @@
+use eframe::egui;
+use eframe::egui::{RichText, TextStyle};
+use flexi_logger::DeferredNow;
+use flexi_logger::writers::LogWriter;
+use log::{Level, LevelFilter, Record};
+use std::collections::{BTreeSet, VecDeque};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::i18n::{tr, trf};
+
+/// flexi_logger 写日志文件的目录
+pub const LOG_DIR: &str = "logs";
+
+/// 内存中最多保留的日志条数，更早的丢掉
+const MAX_ENTRIES: usize = 5000;
+
+static ENTRIES: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    fn line(&self) -> String {
+        format!("{} {:<5} {} {}", self.time, self.level, self.target, self.message)
+    }
+}
+
+/// 把日志额外写到内存里，给界面上的日志面板用
+pub struct MemoryWriter;
+
+impl LogWriter for MemoryWriter {
+    fn write(&self, now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+        let entry = LogEntry {
+            time: now.format("%H:%M:%S%.3f").to_string(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        let mut entries = ENTRIES.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 用系统的文件管理器打开目录
+fn open_directory(path: &Path) -> io::Result<()> {
+    let path = path.canonicalize()?;
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(program).arg(path).spawn()?;
+    Ok(())
+}
+
+/// 日志面板，按级别和 target 过滤
+pub struct LogView {
+    level: LevelFilter,
+    /// 空字符串表示所有 target
+    target: String,
+    status: Option<String>,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            target: String::new(),
+            status: None,
+        }
+    }
+}
+
+impl LogView {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let entries: Vec<LogEntry> = {
+            let entries = ENTRIES.lock().unwrap_or_else(|e| e.into_inner());
+            entries.iter().cloned().collect()
+        };
+        let targets: BTreeSet<&str> = entries.iter().map(|entry| entry.target.as_str()).collect();
+        let visible: Vec<&LogEntry> = entries
+            .iter()
+            .filter(|entry| entry.level <= self.level)
+            .filter(|entry| self.target.is_empty() || entry.target == self.target)
+            .collect();
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label(tr("log_level"));
+            egui::ComboBox::from_id_salt("log_level")
+                .selected_text(self.level.as_str())
+                .show_ui(ui, |ui| {
+                    for level in [
+                        LevelFilter::Error,
+                        LevelFilter::Warn,
+                        LevelFilter::Info,
+                        LevelFilter::Debug,
+                        LevelFilter::Trace,
+                    ] {
+                        ui.selectable_value(&mut self.level, level, level.as_str());
+                    }
+                });
+            ui.label(tr("log_target"));
+            let target_text = if self.target.is_empty() {
+                tr("log_all_targets")
+            } else {
+                &self.target
+            };
+            egui::ComboBox::from_id_salt("log_target")
+                .selected_text(target_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.target, String::new(), tr("log_all_targets"));
+                    for target in &targets {
+                        ui.selectable_value(&mut self.target, target.to_string(), *target);
+                    }
+                });
+            if ui.button(tr("log_copy")).clicked() {
+                let text: Vec<String> = visible.iter().map(|entry| entry.line()).collect();
+                ui.ctx().copy_text(text.join("\n"));
+                self.status = Some(trf("log_copied", &[&visible.len()]));
+            }
+            if ui.button(tr("log_open_folder")).clicked() {
+                self.status = open_directory(Path::new(LOG_DIR))
+                    .err()
+                    .map(|e| trf("log_open_failed", &[&e]));
+            }
+            if ui.button(tr("log_clear")).clicked() {
+                ENTRIES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+            }
+            if let Some(status) = &self.status {
+                ui.weak(status);
+            }
+        });
+        ui.separator();
+
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .id_salt("log_view")
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, visible.len(), |ui, range| {
+                for entry in &visible[range] {
+                    let color = match entry.level {
+                        Level::Error => ui.visuals().error_fg_color,
+                        Level::Warn => ui.visuals().warn_fg_color,
+                        Level::Info => ui.visuals().text_color(),
+                        Level::Debug | Level::Trace => ui.visuals().weak_text_color(),
+                    };
+                    ui.add(
+                        egui::Label::new(RichText::new(entry.line()).monospace().color(color))
+                            .extend(),
+                    );
+                }
+            });
+    }
+}
+
+/// 日志面板标题上显示的警告和错误数
+pub fn problem_count() -> usize {
+    ENTRIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|entry| entry.level <= Level::Warn)
+        .count()
+}
@@ -7,16 +7,94 @@ use flexi_logger::{Age, Cleanup, Criterion, Duplicate, FileSpec, Logger, Naming}
 use log::{LevelFilter, debug, error, info, trace, warn};
 use std::cell::RefCell;
 use std::cmp::PartialEq;
+use std::collections::HashMap;
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, mpsc};
 use std::thread;
+use std::time::{Duration, Instant};
 
 mod audio;
+mod backend;
+mod cli;
+mod editor;
+mod i18n;
+mod logview;
+mod models;
+mod pipeline;
+#[cfg(feature = "playback")]
+mod playback;
+mod profile;
+mod progress;
+mod segments;
+mod settings;
+mod theme;
+mod timeline;
 mod transcribe;
 mod vad;
 
+use i18n::{tr, trf};
+use pipeline::AppState;
+use settings::Settings;
+use progress::{AudioEvent, JobEvent, ProgressEvent, ProgressSink, SubtitleEvent};
+
+/// 窗口宽度(逻辑像素)不小于这个值时，字幕预览显示在右侧边栏
+const WIDE_LAYOUT_WIDTH: f32 = 900.0;
+
+/// 把任务事件转发给界面，并唤醒界面刷新
+struct GuiSink {
+    sender: mpsc::Sender<JobEvent>,
+    ctx: egui::Context,
+}
+
+impl ProgressSink for GuiSink {
+    fn emit(&self, event: JobEvent) {
+        let _ = self.sender.send(event);
+        self.ctx.request_repaint();
+    }
+
+    /// 时间线和字幕编辑器要用
+    fn wants_audio(&self) -> bool {
+        true
+    }
+}
+
+/// 路径停止变化这么久之后才在后台检查，避免边输入边加载
+const CHECK_DELAY: Duration = Duration::from_millis(500);
+
+/// 后台线程发回界面的结果
+enum BackgroundResult {
+    Checksum(PathBuf, Result<String, String>),
+    Silero(PathBuf, Result<(), String>),
+    Streams(PathBuf, Result<Vec<audio::AudioStreamInfo>, String>),
+    Models(PathBuf, models::ModelScan),
+    WhisperInfo(models::WhisperModelInfo),
+}
+
+/// 记录路径最近一次变化的时间
+#[derive(Default)]
+struct Debounce {
+    path: PathBuf,
+    changed: Option<Instant>,
+}
+
+impl Debounce {
+    /// `path` 已经 `CHECK_DELAY` 没有变化时返回 true，否则安排一次重绘再来检查
+    fn settled(&mut self, path: &Path, ctx: &egui::Context) -> bool {
+        if self.path != path {
+            self.path = path.to_path_buf();
+            self.changed = Some(Instant::now());
+        }
+        let elapsed = self.changed.map_or(CHECK_DELAY, |changed| changed.elapsed());
+        if elapsed < CHECK_DELAY {
+            ctx.request_repaint_after(CHECK_DELAY - elapsed);
+            return false;
+        }
+        true
+    }
+}
+
 #[derive(Default)]
 struct App {
     file_dialog: RefCell<FileDialog>,
@@ -26,34 +104,83 @@ struct App {
     output_path: FileOutputData,
     language: String,
     initial_prompt: String,
-    state: Arc<Mutex<AppState>>,
-    progress:Arc<Mutex<f32>>,
-}
-#[derive(PartialOrd, PartialEq)]
-enum AppState{
-    Idle,
-    Resample,
-    VAD,
-    Whisper,
-    Saving,
-    Finished
-}
-impl Default for AppState {
-    fn default() -> Self {
-        Self::Idle
-    }
+    vad_backend: vad::VadBackend,
+    vad_sample_rate: u32,
+    vad_settings: vad::VadSettings,
+    whisper_settings: transcribe::WhisperSettings,
+    audio_streams: Vec<audio::AudioStreamInfo>,
+    selected_streams: Vec<usize>,
+    /// `audio_streams` 是从哪个文件读出来的
+    streams_source: PathBuf,
+    /// 正在后台读取 `streams_source` 的音频流
+    streams_loading: bool,
+    streams_debounce: Debounce,
+    channel_mode: audio::ChannelMode,
+    /// 分声道时的说话人标签，逗号分隔
+    speakers: String,
+    /// 只转录这一段，格式为 [[hh:]mm:]ss，留空表示不限制
+    range_from: String,
+    range_to: String,
+    job_mode: pipeline::JobMode,
+    /// 文件对话框下次打开时所在的目录
+    last_directory: Option<PathBuf>,
+    /// 模型目录，以及从中扫描到的模型
+    models_dir: FileSelectionData,
+    models_source: PathBuf,
+    models: models::ModelScan,
+    models_debounce: Debounce,
+    /// 当前 whisper 模型的文件头信息，路径变化时在后台重新读取
+    whisper_info: Option<models::WhisperModelInfo>,
+    /// 最近一次读取文件头的 whisper 模型路径
+    whisper_source: PathBuf,
+    whisper_debounce: Debounce,
+    /// SileroVAD 模型的检查结果，路径变化时在后台重新检查，`None` 表示还在检查
+    silero_check: Option<(PathBuf, Option<Result<(), String>>)>,
+    silero_debounce: Debounce,
+    /// 模型文件的校验结果，`None` 表示还在计算
+    checksums: HashMap<PathBuf, Option<Result<String, String>>>,
+    background_sender: Option<mpsc::Sender<BackgroundResult>>,
+    background_receiver: Option<mpsc::Receiver<BackgroundResult>>,
+    /// 当前选中的配置名，以及 profiles 目录下已有的配置
+    profile_name: String,
+    profiles: Vec<String>,
+    finished_outputs: Vec<PathBuf>,
+    state: AppState,
+    /// 正在运行的任务发来的事件
+    events: Option<mpsc::Receiver<JobEvent>>,
+    last_progress: Option<ProgressEvent>,
+    warnings: Vec<String>,
+    job_error: Option<String>,
+    /// 转录过程中实时产生的字幕
+    transcript: Vec<SubtitleEvent>,
+    /// 上一次任务解码出的音频，字幕编辑器回放用
+    job_audio: Vec<AudioEvent>,
+    editor: Option<editor::TranscriptEditor>,
+    timeline: timeline::Timeline,
+    show_timeline: bool,
+    log_view: logview::LogView,
+    show_log: bool,
+    show_about: bool,
+    /// 界面语言，`None` 表示跟随系统
+    ui_language: Option<i18n::Locale>,
+    theme: theme::Theme,
+    /// 已经应用到界面上的主题，和 `theme` 不同时重新应用
+    applied_theme: Option<theme::Theme>,
 }
 
 #[derive(Default)]
 struct FileSelectionData {
-    hint: String,
+    /// 字符串表中的键
+    hint: &'static str,
     path: PathBuf,
     path_string: String,
     // default_filename: String,
     ongoing: bool,
+    /// 选择的是目录而不是文件
+    directory: bool,
 }
 impl FileSelectionData {
-    fn new(hint: String) -> Self {
+    fn new(hint: &'static str) -> Self {
         Self {
             hint,
             ongoing: false,
@@ -61,17 +188,22 @@ impl FileSelectionData {
             ..Self::default()
         }
     }
+    fn set_path(&mut self, path: PathBuf) {
+        self.path_string = path.to_string_lossy().into_owned();
+        self.path = path;
+    }
 }
 #[derive(Default)]
 struct FileOutputData {
-    hint: String,
+    /// 字符串表中的键
+    hint: &'static str,
     path: PathBuf,
     path_string: String,
     default_filename: String,
     ongoing: bool,
 }
 impl FileOutputData {
-    fn new(hint: String, default_filename: String) -> Self {
+    fn new(hint: &'static str, default_filename: String) -> Self {
         Self {
             hint,
             ongoing: false,
@@ -79,23 +211,213 @@ impl FileOutputData {
             ..Self::default()
         }
     }
+    fn set_path(&mut self, path: PathBuf) {
+        self.path_string = path.to_string_lossy().into_owned();
+        self.path = path;
+    }
 }
 
 impl App {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // Customize egui here with cc.egui_ctx.set_fonts and cc.egui_ctx.set_visuals.
-        // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
         Self::load_chinese_fonts(cc);
-        Self {
+        let settings: Settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        let mut app = Self {
             file_dialog: RefCell::new(FileDialog::new().as_modal(true).default_size([664.,200.])),
-            audio_path: FileSelectionData::new("音频文件".to_string()),
-            whisper_path: FileSelectionData::new("Whisper模型(ggml-*.bin)".to_string()),
-            silero_vad_path: FileSelectionData::new("SileroVAD模型(silero_vad.onnx)".to_string()),
-            output_path: FileOutputData::new("输出文件".to_string(), String::new()),
-            language:"zh".to_string(),
+            audio_path: FileSelectionData::new("file_audio"),
+            whisper_path: FileSelectionData::new("file_whisper"),
+            silero_vad_path: FileSelectionData::new("file_silero"),
+            output_path: FileOutputData::new("file_output", String::new()),
+            models_dir: FileSelectionData {
+                directory: true,
+                ..FileSelectionData::new("file_models_dir")
+            },
             ..Self::default()
+        };
+        let (sender, receiver) = mpsc::channel();
+        app.background_sender = Some(sender);
+        app.background_receiver = Some(receiver);
+        app.apply_settings(settings);
+        app.profiles = profile::list_profiles();
+        app
+    }
+    /// 当前界面上需要保存的设置
+    fn settings(&self) -> Settings {
+        Settings {
+            audio_path: self.audio_path.path.clone(),
+            whisper_path: self.whisper_path.path.clone(),
+            silero_vad_path: self.silero_vad_path.path.clone(),
+            output_path: self.output_path.path.clone(),
+            models_dir: self.models_dir.path.clone(),
+            last_directory: self.last_directory.clone(),
+            language: self.language.clone(),
+            initial_prompt: self.initial_prompt.clone(),
+            vad_backend: self.vad_backend,
+            vad_sample_rate: self.vad_sample_rate,
+            vad_settings: self.vad_settings,
+            whisper_settings: self.whisper_settings,
+            channel_mode: self.channel_mode,
+            speakers: self.speakers.clone(),
+            job_mode: self.job_mode,
+            show_timeline: self.show_timeline,
+            show_log: self.show_log,
+            profile: self.profile_name.clone(),
+            ui_language: self.ui_language,
+            theme: self.theme,
+        }
+    }
+    fn apply_settings(&mut self, settings: Settings) {
+        self.audio_path.set_path(settings.audio_path);
+        self.whisper_path.set_path(settings.whisper_path);
+        self.silero_vad_path.set_path(settings.silero_vad_path);
+        self.output_path.set_path(settings.output_path);
+        self.models_dir.set_path(settings.models_dir);
+        self.last_directory = settings.last_directory;
+        self.language = settings.language;
+        self.initial_prompt = settings.initial_prompt;
+        self.vad_backend = settings.vad_backend;
+        self.vad_sample_rate = settings.vad_sample_rate;
+        self.vad_settings = settings.vad_settings;
+        self.whisper_settings = settings.whisper_settings;
+        self.channel_mode = settings.channel_mode;
+        self.speakers = settings.speakers;
+        self.job_mode = settings.job_mode;
+        self.show_timeline = settings.show_timeline;
+        self.show_log = settings.show_log;
+        self.profile_name = settings.profile;
+        self.ui_language = settings.ui_language;
+        self.theme = settings.theme;
+        i18n::set_locale(self.ui_language.unwrap_or_else(i18n::Locale::system));
+    }
+    /// 当前界面上属于配置的那部分设置
+    fn profile(&self) -> profile::Profile {
+        profile::Profile {
+            whisper_path: Some(self.whisper_path.path.clone()),
+            vad_backend: Some(self.vad_backend),
+            vad_path: Some(self.silero_vad_path.path.clone()),
+            vad_sample_rate: Some(self.vad_sample_rate),
+            vad: Some(self.vad_settings),
+            whisper: Some(self.whisper_settings),
+            language: Some(self.language.clone()),
+            initial_prompt: Some(self.initial_prompt.clone()),
+            mode: Some(self.job_mode),
+        }
+    }
+    /// 只修改配置中写了的设置
+    fn apply_profile(&mut self, profile: profile::Profile) {
+        if let Some(path) = profile.whisper_path {
+            self.whisper_path.set_path(path);
+        }
+        if let Some(backend) = profile.vad_backend {
+            self.vad_backend = backend;
+        }
+        if let Some(path) = profile.vad_path {
+            self.silero_vad_path.set_path(path);
+        }
+        if let Some(sample_rate) = profile.vad_sample_rate {
+            self.vad_sample_rate = sample_rate;
+        }
+        if let Some(settings) = profile.vad {
+            self.vad_settings = settings;
+        }
+        if let Some(settings) = profile.whisper {
+            self.whisper_settings = settings;
+        }
+        if let Some(language) = profile.language {
+            self.language = language;
+        }
+        if let Some(prompt) = profile.initial_prompt {
+            self.initial_prompt = prompt;
+        }
+        if let Some(mode) = profile.mode {
+            self.job_mode = mode;
+        }
+    }
+    fn profile_selection(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(tr("profile"));
+            let selected_text = if self.profile_name.is_empty() {
+                tr("profile_none")
+            } else {
+                &self.profile_name
+            };
+            let combo = egui::ComboBox::from_id_salt("profile")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    let mut picked = None;
+                    for name in &self.profiles {
+                        if ui.selectable_label(*name == self.profile_name, name).clicked() {
+                            picked = Some(name.clone());
+                        }
+                    }
+                    picked
+                });
+            // 每次打开下拉框时重新扫描目录
+            if combo.response.clicked() {
+                self.profiles = profile::list_profiles();
+            }
+            if let Some(Some(name)) = combo.inner {
+                match profile::load(&name) {
+                    Ok(loaded) => {
+                        info!("使用配置 {}", name);
+                        self.apply_profile(loaded);
+                        self.profile_name = name;
+                    }
+                    Err(e) => self.warnings.push(trf("profile_load_failed", &[&name, &e])),
+                }
+            }
+            ui.add(
+                TextEdit::singleline(&mut self.profile_name)
+                    .hint_text(tr("profile_name"))
+                    .desired_width(120.0),
+            );
+            if ui.button(tr("profile_save")).clicked() {
+                match profile::save(&self.profile_name, &self.profile()) {
+                    Ok(()) => {
+                        info!("保存配置 {}", self.profile_name);
+                        self.profiles = profile::list_profiles();
+                    }
+                    Err(e) => self.warnings.push(trf("profile_save_failed", &[&e])),
+                }
+            }
+            if ui
+                .add_enabled(self.profiles.contains(&self.profile_name), Button::new(tr("delete")))
+                .clicked()
+            {
+                match profile::delete(&self.profile_name) {
+                    Ok(()) => {
+                        self.profiles = profile::list_profiles();
+                        self.profile_name.clear();
+                    }
+                    Err(e) => self.warnings.push(trf("profile_delete_failed", &[&e])),
+                }
+            }
+        });
+    }
+    /// 界面语言的下拉框，选择后立即生效
+    fn language_selection(&mut self, ui: &mut egui::Ui) {
+        let name = |language: Option<i18n::Locale>| {
+            language.map_or(tr("ui_language_system"), |locale| locale.native_name())
+        };
+        let mut selected = self.ui_language;
+        ui.label(tr("ui_language"));
+        egui::ComboBox::from_id_salt("ui_language")
+            .selected_text(name(selected))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut selected, None, name(None));
+                for locale in i18n::Locale::ALL {
+                    ui.selectable_value(&mut selected, Some(locale), name(Some(locale)));
+                }
+            });
+        if selected != self.ui_language {
+            self.ui_language = selected;
+            i18n::set_locale(selected.unwrap_or_else(i18n::Locale::system));
+            info!("界面语言: {}", i18n::locale());
         }
     }
     fn load_chinese_fonts(cc: &eframe::CreationContext<'_>) {
@@ -126,13 +448,357 @@ impl App {
         // let mut ctx = egui::CtxRef::default();
         cc.egui_ctx.set_fonts(fonts);
     }
+    fn poll_background(&mut self) {
+        let Some(receiver) = &self.background_receiver else {
+            return;
+        };
+        let results: Vec<BackgroundResult> = receiver.try_iter().collect();
+        for result in results {
+            match result {
+                BackgroundResult::Checksum(path, result) => {
+                    self.checksums.insert(path, Some(result));
+                }
+                // 检查期间路径又变了的结果直接丢掉
+                BackgroundResult::Silero(path, result) => {
+                    if let Some((checked, pending)) = &mut self.silero_check
+                        && *checked == path
+                    {
+                        if let Err(e) = &result {
+                            warn!("SileroVAD模型无效: {}", e);
+                        }
+                        *pending = Some(result);
+                    }
+                }
+                BackgroundResult::Streams(path, result) => {
+                    if path != self.streams_source {
+                        continue;
+                    }
+                    self.streams_loading = false;
+                    match result {
+                        Ok(streams) => {
+                            debug!("找到{}条音频流", streams.len());
+                            self.selected_streams = streams
+                                .iter()
+                                .filter(|stream| stream.best)
+                                .map(|stream| stream.index)
+                                .collect();
+                            self.audio_streams = streams;
+                        }
+                        Err(e) => warn!("无法读取音频流: {}", e),
+                    }
+                }
+                BackgroundResult::Models(path, scan) => {
+                    if path != self.models_source {
+                        continue;
+                    }
+                    debug!(
+                        "模型目录中找到{}个Whisper模型，{}个SileroVAD模型",
+                        scan.whisper.len(),
+                        scan.silero.len()
+                    );
+                    self.models = scan;
+                }
+                BackgroundResult::WhisperInfo(info) => {
+                    if info.path == self.whisper_source {
+                        self.whisper_info = Some(info);
+                    }
+                }
+            }
+        }
+    }
+    /// 在后台线程里运行 `task`，结果通过 `background_receiver` 发回来
+    fn spawn_background(
+        &self,
+        ctx: &egui::Context,
+        task: impl FnOnce() -> BackgroundResult + Send + 'static,
+    ) {
+        let Some(sender) = self.background_sender.clone() else {
+            return;
+        };
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(task());
+            ctx.request_repaint();
+        });
+    }
+    /// 在后台线程里计算模型文件的校验和
+    fn verify_model(&mut self, path: PathBuf, ctx: &egui::Context) {
+        self.checksums.insert(path.clone(), None);
+        self.spawn_background(ctx, move || {
+            let result = models::sha1_file(&path).map_err(|e| e.to_string());
+            BackgroundResult::Checksum(path, result)
+        });
+    }
+    /// 模型目录里找到的模型、当前模型的信息和校验结果
+    /// 路径变化后重新读取音频流和模型信息。面板折叠时不会绘制对应的控件，所以每帧都要检查
+    fn refresh_sources(&mut self, ctx: &egui::Context) {
+        if self.audio_path.path != self.streams_source
+            && self.streams_debounce.settled(&self.audio_path.path, ctx)
+        {
+            self.refresh_audio_streams(ctx);
+        }
+        let silero_path = &self.silero_vad_path.path;
+        if self.vad_backend.needs_model()
+            && silero_path.is_file()
+            && self.silero_check.as_ref().map(|(checked, _)| checked) != Some(silero_path)
+            && self.silero_debounce.settled(silero_path, ctx)
+        {
+            let path = silero_path.clone();
+            self.silero_check = Some((path.clone(), None));
+            self.spawn_background(ctx, move || {
+                let result = models::validate_silero(&path);
+                BackgroundResult::Silero(path, result)
+            });
+        }
+        if self.models_dir.path != self.models_source
+            && self.models_debounce.settled(&self.models_dir.path, ctx)
+        {
+            self.models_source = self.models_dir.path.clone();
+            let path = self.models_source.clone();
+            self.spawn_background(ctx, move || {
+                let scan = models::scan(&path);
+                BackgroundResult::Models(path, scan)
+            });
+        }
+        if self.whisper_path.path != self.whisper_source
+            && self.whisper_debounce.settled(&self.whisper_path.path, ctx)
+        {
+            self.whisper_source = self.whisper_path.path.clone();
+            self.whisper_info = None;
+            if self.whisper_source.is_file() {
+                let path = self.whisper_source.clone();
+                self.spawn_background(ctx, move || {
+                    BackgroundResult::WhisperInfo(models::WhisperModelInfo::inspect(&path))
+                });
+            }
+        }
+    }
+    /// 当前 whisper 模型的文件头信息，路径改过之后还没读完时是 `None`
+    fn current_whisper_info(&self) -> Option<&models::WhisperModelInfo> {
+        self.whisper_info
+            .as_ref()
+            .filter(|info| info.path == self.whisper_path.path)
+    }
+    fn model_manager(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if !self.models.whisper.is_empty() {
+                let selected = self
+                    .current_whisper_info()
+                    .map_or(tr("models_pick_whisper").to_string(), |info| info.file_name());
+                let mut picked = None;
+                egui::ComboBox::from_id_salt("found_whisper_models")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for model in &self.models.whisper {
+                            let text = format!("{}  {}", model.file_name(), model.summary());
+                            if ui
+                                .selectable_label(model.path == self.whisper_path.path, text)
+                                .clicked()
+                            {
+                                picked = Some(model.clone());
+                            }
+                        }
+                    });
+                // 扫描时已经读过文件头，不用再读一遍
+                if let Some(model) = picked {
+                    self.whisper_path.set_path(model.path.clone());
+                    self.whisper_source = model.path.clone();
+                    self.whisper_info = Some(model);
+                }
+            }
+            if let Some(info) = self.current_whisper_info() {
+                if let Err(e) = &info.header {
+                    ui.colored_label(ui.visuals().error_fg_color, trf("model_invalid", &[e]));
+                    return;
+                }
+                ui.weak(info.summary());
+                let path = info.path.clone();
+                match self.checksums.get(&path) {
+                    None => {
+                        if ui.small_button(tr("model_verify")).clicked() {
+                            self.verify_model(path, ui.ctx());
+                        }
+                    }
+                    Some(None) => {
+                        ui.spinner();
+                        ui.weak(tr("model_verifying"));
+                    }
+                    Some(Some(Ok(sha1))) => match models::identify(sha1) {
+                        Some(name) => {
+                            ui.label(trf("model_official", &[&name]));
+                        }
+                        None => {
+                            ui.colored_label(ui.visuals().warn_fg_color, tr("model_unknown"))
+                                .on_hover_text(format!("SHA-1: {}", sha1));
+                        }
+                    },
+                    Some(Some(Err(e))) => {
+                        ui.colored_label(ui.visuals().error_fg_color, trf("model_verify_failed", &[e]));
+                    }
+                }
+            }
+        });
+        if let Some(warning) = self
+            .current_whisper_info()
+            .and_then(|info| info.language_warning(&self.language))
+        {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+    }
+    /// 当前 SileroVAD 模型的检查结果，`None` 表示还没有检查完
+    fn silero_status(&self) -> Option<Result<(), String>> {
+        let path = &self.silero_vad_path.path;
+        if !path.is_file() {
+            return Some(Err(tr("silero_missing").to_string()));
+        }
+        match &self.silero_check {
+            Some((checked, result)) if checked == path => result.clone(),
+            _ => None,
+        }
+    }
+    fn poll_events(&mut self) {
+        let Some(receiver) = &self.events else {
+            return;
+        };
+        let mut events = Vec::new();
+        let disconnected = loop {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(mpsc::TryRecvError::Empty) => break false,
+                Err(mpsc::TryRecvError::Disconnected) => break true,
+            }
+        };
+        for event in events {
+            match event {
+                JobEvent::Stage(stage) => self.state = stage,
+                JobEvent::Progress(progress) => self.last_progress = Some(progress),
+                JobEvent::Warning(message) => self.warnings.push(message),
+                JobEvent::Subtitle(subtitle) => self.transcript.push(subtitle),
+                JobEvent::Audio(audio) => self.job_audio.push(audio),
+                JobEvent::Error(message) => {
+                    self.job_error = Some(message);
+                    self.state = AppState::Idle;
+                    self.events = None;
+                }
+                JobEvent::Finished { outputs, summary } => {
+                    self.finished_outputs = outputs;
+                    self.last_progress = Some(summary);
+                    self.state = AppState::Finished;
+                    self.events = None;
+                }
+            }
+        }
+        // 任务线程没有发出 Finished/Error 就退出了，通常是 panic
+        if disconnected && self.events.is_some() {
+            error!("Job thread exited without reporting a result");
+            self.job_error = Some(tr("job_disconnected").to_string());
+            self.state = AppState::Idle;
+            self.events = None;
+        }
+    }
+    fn open_editor(&mut self, output: &Path) {
+        let tracks = self
+            .job_audio
+            .iter()
+            .filter(|audio| audio.output.as_path() == output)
+            .cloned()
+            .collect();
+        match editor::TranscriptEditor::open(output, tracks) {
+            Ok(editor) => self.editor = Some(editor),
+            Err(e) => {
+                warn!("无法打开字幕文件: {}", e);
+                self.warnings.push(trf("editor_open_failed", &[&e]));
+            }
+        }
+    }
+    /// 拖进窗口的文件：.bin 是 whisper 模型，.onnx 是 Silero 模型，其余当作音频/视频
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped: Vec<PathBuf> = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        let mut audio_set = false;
+        for path in dropped {
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+            match extension.as_deref() {
+                Some("bin") => {
+                    debug!("拖入Whisper模型: {}", path.to_string_lossy());
+                    self.whisper_path.set_path(path);
+                }
+                Some("onnx") => {
+                    debug!("拖入SileroVAD模型: {}", path.to_string_lossy());
+                    self.vad_backend = vad::VadBackend::Silero;
+                    self.silero_vad_path.set_path(path);
+                }
+                _ if path.is_file() && !audio_set => {
+                    debug!("拖入音频文件: {}", path.to_string_lossy());
+                    self.audio_path.set_path(path);
+                    audio_set = true;
+                }
+                _ => warn!("忽略拖入的文件: {}", path.to_string_lossy()),
+            }
+        }
+    }
+    /// 有文件悬停在窗口上时盖一层提示
+    fn dropping_overlay(ctx: &egui::Context) {
+        if !ctx.input(|input| !input.raw.hovered_files.is_empty()) {
+            return;
+        }
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            Id::new("file_drop_target"),
+        ));
+        let screen_rect = ctx.screen_rect();
+        painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(192));
+        painter.text(
+            screen_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            tr("drop_hint"),
+            TextStyle::Heading.resolve(&ctx.style()),
+            egui::Color32::WHITE,
+        );
+    }
+    /// 在后台读取音频流，结果在 `poll_background` 里处理
+    fn refresh_audio_streams(&mut self, ctx: &egui::Context) {
+        self.streams_source = self.audio_path.path.clone();
+        self.audio_streams.clear();
+        self.selected_streams.clear();
+        self.streams_loading = self.streams_source.is_file();
+        if !self.streams_loading {
+            return;
+        }
+        let path = self.streams_source.clone();
+        self.spawn_background(ctx, move || {
+            let result = audio::list_audio_streams(&path).map_err(|e| e.to_string());
+            BackgroundResult::Streams(path, result)
+        });
+    }
 }
 
 
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings());
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         use egui::text::{LayoutJob, TextFormat};
-        catppuccin_egui::set_theme(ctx, catppuccin_egui::MACCHIATO);
+        if self.applied_theme != Some(self.theme) {
+            debug!("使用主题 {}", self.theme);
+            self.theme.apply(ctx);
+            self.applied_theme = Some(self.theme);
+        }
+        self.poll_events();
+        self.poll_background();
+        self.refresh_sources(ctx);
+        self.handle_dropped_files(ctx);
+        Self::dropping_overlay(ctx);
         self.file_dialog.borrow_mut().update(ctx);
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
             // 使用 vertical_centered，它能完美地居中其内部的每个独立控件。
@@ -146,7 +812,7 @@ impl eframe::App for App {
 
                 // 添加第一段普通文本
                 job.append(
-                    "一个使用Whisper和SileroVAD的实用语音转文字小程序 ", // 在末尾加一个空格
+                    tr("app_description"), // 在末尾加一个空格
                     0.0,
                     TextFormat {
                         font_id: font_id.clone(),
@@ -157,7 +823,7 @@ impl eframe::App for App {
 
                 // 添加第二段 weak 样式的文本
                 job.append(
-                    "本程序使用了MiSans-Regular字体",
+                    tr("app_font"),
                     0.0,
                     TextFormat {
                         font_id: font_id.clone(),
@@ -168,205 +834,614 @@ impl eframe::App for App {
 
                 // 将这个 job 作为一个单一的 label 添加到 UI 中。现在它是一个整体，可以被轻松居中。
                 ui.label(job);
+                ui.horizontal(|ui| {
+                    if ui.small_button(tr("about")).clicked() {
+                        self.show_about = true;
+                    }
+                    if ui.small_button(tr("reset_settings")).clicked() {
+                        info!("恢复默认设置");
+                        self.apply_settings(Settings::default());
+                    }
+                    self.language_selection(ui);
+                    let problems = logview::problem_count();
+                    let log_text = if problems > 0 {
+                        trf("log_with_problems", &[&problems])
+                    } else {
+                        tr("log").to_string()
+                    };
+                    ui.toggle_value(&mut self.show_log, log_text);
+                    ui.label(tr("theme"));
+                    egui::ComboBox::from_id_salt("theme")
+                        .selected_text(self.theme.name())
+                        .show_ui(ui, |ui| {
+                            for theme in theme::Theme::ALL {
+                                ui.selectable_value(&mut self.theme, theme, theme.name());
+                            }
+                        });
+                });
             });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            Self::file_selection(ui, &self.file_dialog, &mut self.audio_path);
-            Self::file_selection(ui, &self.file_dialog, &mut self.whisper_path);
-            Self::file_selection(ui, &self.file_dialog, &mut self.silero_vad_path);
-            ui.separator();
-            ui.horizontal(|ui| {
-                ui.centered_and_justified(|ui| {
-                    ui.horizontal(|ui| {
-                        ui.label("语言");
-                        // ui.centered_and_justified(|ui| {
-                        ui.text_edit_singleline(&mut self.language);
-                        // })
-                    })
-                });
-                ui.centered_and_justified(|ui| {
-                    ui.horizontal(|ui| {
-                        ui.label("Initial Prompt (Optional)");
-                        // ui.centered_and_justified(|ui| {
-                        ui.text_edit_singleline(&mut self.initial_prompt);
-                        // })
-                    })
-                });
+        let wide = ctx.screen_rect().width() >= WIDE_LAYOUT_WIDTH;
+        egui::TopBottomPanel::bottom("log")
+            .resizable(true)
+            .default_height(180.0)
+            .show_animated(ctx, self.show_log, |ui| self.log_view.ui(ui));
+        if self.editor.is_none() {
+            egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+                ui.add_space(4.0);
+                self.start_button(ui);
+                self.status(ui);
             });
-            ui.separator();
-            ui.horizontal(|ui| {
-                if ui.button("输出文件").clicked() {
-                    debug!("开始选择输出文件");
-                    self.output_path.default_filename = self
-                        .audio_path
-                        .path
-                        .file_stem()
-                        .unwrap()
-                        .to_string_lossy()
-                        .into_owned();
-                    self.output_path.ongoing = true;
-
-                    //真服了，用了refcell 结果告诉我 default filename 是 save file mode 用的
-                    self.file_dialog.replace(
-                        self.file_dialog
-                            .take()
-                            .title("打开输出文件")
-                            .default_file_name(&self.output_path.default_filename)
-                            .add_save_extension("SubRip Subtitle", "srt")
-                            .default_save_extension("SubRip Subtitle"),
-                    );
-                    self.file_dialog.borrow_mut().save_file();
-                };
-                if self.output_path.ongoing
-                    && let Some(path) = self.file_dialog.borrow_mut().take_picked()
-                {
-                    self.output_path.path = path.to_path_buf();
-                    self.output_path.path_string =
-                        self.output_path.path.to_string_lossy().into_owned();
-                    debug!("保存到{}文件", self.output_path.hint);
-                    self.output_path.ongoing = false;
-                } else {
-                    if self.output_path.path_string != self.output_path.path.to_string_lossy() {
-                        debug!("检测到文件输入框变更: {}", self.output_path.path_string);
-                        self.output_path.path =
-                            self.output_path.path_string.clone().try_into().unwrap();
-                        debug!(
-                            "当前PathBuf内容 {}",
-                            self.output_path.path.to_string_lossy()
-                        );
-                    }
+            egui::SidePanel::right("preview")
+                .resizable(true)
+                .default_width(320.0)
+                .width_range(200.0..=640.0)
+                .show_animated(ctx, wide && !self.transcript.is_empty(), |ui| {
+                    ui.heading(tr("preview"));
+                    ui.separator();
+                    Self::transcript_preview(ui, &self.transcript, f32::INFINITY);
+                });
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(editor) = &mut self.editor {
+                if editor.ui(ui) {
+                    self.editor = None;
                 }
-                ui.centered_and_justified(|ui| {
-                    let file_text_edit = TextEdit::singleline(&mut self.output_path.path_string)
-                        .hint_text("请选择".to_string() + &self.output_path.hint);
-                    ui.add(file_text_edit)
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .id_salt("settings")
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    self.profile_selection(ui);
+                    ui.separator();
+                    egui::CollapsingHeader::new(tr("section_input"))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            self.input_section(ui);
+                            self.output_selection(ui);
+                        });
+                    egui::CollapsingHeader::new(tr("section_models"))
+                        .default_open(true)
+                        .show(ui, |ui| self.model_section(ui));
+                    egui::CollapsingHeader::new(tr("section_advanced"))
+                        .default_open(true)
+                        .show(ui, |ui| self.advanced_section(ui));
+                    // 窗口太窄放不下侧边栏时，预览放在设置下面
+                    if !wide && !self.transcript.is_empty() {
+                        egui::CollapsingHeader::new(tr("preview"))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                Self::transcript_preview(ui, &self.transcript, 240.0)
+                            });
+                    }
                 });
+        });
+        egui::Window::new(tr("timeline"))
+            .id(Id::new("vad_timeline"))
+            .open(&mut self.show_timeline)
+            .default_width(680.0)
+            .show(ctx, |ui| {
+                self.timeline.ui(ui, &self.job_audio, &mut self.vad_settings);
             });
-            ui.separator();
-            let should_start = if self.audio_path.path.is_file()
-                && self.whisper_path.path.is_file()
-                && self.silero_vad_path.path.is_file()
-                && !self.output_path.path_string.is_empty()
-                && !self.language.is_empty()
-                && *self.state.lock().unwrap() == AppState::Idle
-            {
-                true
-            } else {
-                false
-            };
-            ui.horizontal(|ui| {
-               if ui.add_enabled(
-                    should_start,
-                    Button::new(RichText::new("开始转录").size(14.0).strong())
-                        .corner_radius(5.0)
-                        .min_size([710.0, 32.8].into()),
-                ).clicked(){
-                   debug!("开始转录");
-                   thread::spawn({
-                        let state = Arc::clone(&self.state);
-                        let progress = Arc::clone(&self.progress);
-                       let audio_path = self.audio_path.path.clone();
-                       let vad_path=self.silero_vad_path.path_string.clone();
-                       let whisper_path=self.whisper_path.path_string.clone();
-                       let language=self.language.clone();
-                       let initial_prompt=self.initial_prompt.clone();
-                       let output_path=self.output_path.path.clone();
-                        move || {
-                            {*progress.lock().unwrap() =  0.;}
-                            {*state.lock().unwrap() = AppState::Resample;}
-
-                            let mut resampled =audio::do_resample(progress.clone(), 16000, &audio_path).unwrap();
-                            {*progress.lock().unwrap() =  1.;}
-
-                            {*progress.lock().unwrap() =  0.;}
-                            {*state.lock().unwrap() = AppState::VAD;}
-
-                            let active=vad::do_vad(progress.clone(), 16000, &vad_path, &mut resampled).unwrap();
-                            {*progress.lock().unwrap() =  1.;}
-
-                            {*progress.lock().unwrap() =  0.;}
-                            {*state.lock().unwrap() = AppState::Whisper;}
-
-                            let subs=transcribe::do_whisper(progress.clone(), &whisper_path, &active, &language, &initial_prompt).unwrap();
-                            {*progress.lock().unwrap() =  1.;}
-
-                            {*progress.lock().unwrap() =  0.;}
-                            {*state.lock().unwrap() = AppState::Saving;}
-
-                            subs.write_to_file(&output_path, None).unwrap();
-                            {*progress.lock().unwrap() =  1.;}
-
-                            {*progress.lock().unwrap() =  0.;}
-                            {*state.lock().unwrap() = AppState::Finished;}
-                        }
-                    });
-               };
+        if self.show_about {
+            let modal = Modal::new(Id::from("about_modal"));
+            modal.show(ctx, |ui| {
+                ui.heading(tr("about_title"));
+                ui.label(trf("about_version", &[&env!("CARGO_PKG_VERSION")]));
+                ui.separator();
+                ui.label(trf("about_whisper_backends", &[&backend::whisper_backends().join(", ")]));
+                ui.label(trf("about_vad_backends", &[&backend::vad_backends().join(", ")]));
+                ui.separator();
+                ui.vertical_centered_justified(|ui| {
+                    if ui.button("OK").clicked() {
+                        self.show_about = false;
+                    };
+                });
             });
-            ui.separator();
-                ui.label(
-                    match *self.state.lock().unwrap() {
-                        AppState::Idle => "空闲".to_string(),
-                        AppState::VAD => "正在检测语音活动...".to_string(),
-                        AppState::Whisper => "正在转录...".to_string(),
-                        AppState::Resample=>"正在重采样".to_string(),
-                        AppState::Saving=>"正在保存".to_string(),
-                        AppState::Finished=>"完成".to_string(),
-                    }
-                );
-            // ui.label("Label");
-            ui.add(ProgressBar::new(*self.progress.lock().unwrap()).show_percentage())
-
-
-
-            // ui.top
-        });
-        if *self.state.lock().unwrap()==AppState::Finished{
+        }
+        if self.state==AppState::Finished{
             let modal = Modal::new(Id::from("my_modal"));
 
             // What goes inside the modal
+            let mut edit = None;
             modal.show(ctx,|ui| {
-                ui.heading("转录已完成");
+                ui.heading(tr("finished_title"));
                 ui.separator();
-                ui.strong("文件已保存到".to_string()+ &self.output_path.path_string);
+                for output in &self.finished_outputs {
+                    ui.horizontal(|ui| {
+                        ui.strong(trf("finished_saved", &[&output.to_string_lossy()]));
+                        if is_srt(output) && ui.small_button(tr("edit")).clicked() {
+                            edit = Some(output.clone());
+                        }
+                    });
+                }
                 ui.separator();
                 ui.vertical_centered_justified(|ui| {
                     if ui.button("OK").clicked(){
-                        *self.state.lock().unwrap()=AppState::Idle;
+                        self.state=AppState::Idle;
                     };
                 });
 
 
             });
+            if let Some(output) = edit {
+                self.state = AppState::Idle;
+                self.open_editor(&output);
+            }
+        }
+        if let Some(message) = &self.job_error {
+            let modal = Modal::new(Id::from("error_modal"));
+            let mut close = false;
+            modal.show(ctx, |ui| {
+                ui.heading(tr("failed_title"));
+                ui.separator();
+                ui.colored_label(ui.visuals().error_fg_color, message);
+                ui.separator();
+                ui.vertical_centered_justified(|ui| {
+                    if ui.button("OK").clicked() {
+                        close = true;
+                    };
+                });
+            });
+            if close {
+                self.job_error = None;
+            }
         }
     }
 }
 impl App {
+    /// 音频文件、音频流、声道、范围和模式
+    fn input_section(&mut self, ui: &mut egui::Ui) {
+        Self::file_selection(ui, &self.file_dialog, &mut self.last_directory, &mut self.audio_path);
+        if self.streams_loading {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.weak(tr("streams_loading"));
+            });
+        }
+        if self.audio_streams.len() > 1 {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(tr("streams"));
+                for stream in &self.audio_streams {
+                    let mut checked = self.selected_streams.contains(&stream.index);
+                    if ui.checkbox(&mut checked, stream.to_string()).changed() {
+                        if checked {
+                            self.selected_streams.push(stream.index);
+                            self.selected_streams.sort();
+                        } else {
+                            self.selected_streams.retain(|index| *index != stream.index);
+                        }
+                    }
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label(tr("channels"));
+            let mode_text = |mode: audio::ChannelMode| match mode {
+                audio::ChannelMode::Downmix => tr("channels_downmix").to_string(),
+                audio::ChannelMode::Pick(channel) => trf("channels_pick", &[&(channel + 1)]),
+                audio::ChannelMode::Split => tr("channels_split").to_string(),
+            };
+            egui::ComboBox::from_id_salt("channel_mode")
+                .selected_text(mode_text(self.channel_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        audio::ChannelMode::Downmix,
+                        audio::ChannelMode::Pick(0),
+                        audio::ChannelMode::Split,
+                    ] {
+                        let selected = std::mem::discriminant(&self.channel_mode)
+                            == std::mem::discriminant(&mode);
+                        if ui.selectable_label(selected, mode_text(mode)).clicked() && !selected {
+                            self.channel_mode = mode;
+                        }
+                    }
+                });
+            match &mut self.channel_mode {
+                audio::ChannelMode::Pick(channel) => {
+                    let mut number = *channel + 1;
+                    ui.add(egui::DragValue::new(&mut number).range(1..=64).prefix(tr("channel_prefix")));
+                    *channel = number - 1;
+                }
+                audio::ChannelMode::Split => {
+                    ui.add(
+                        TextEdit::singleline(&mut self.speakers)
+                            .hint_text(tr("speakers_hint")),
+                    );
+                }
+                audio::ChannelMode::Downmix => {}
+            }
+        });
+        let range = audio::TimeRange::parse(&self.range_from, &self.range_to);
+        ui.horizontal(|ui| {
+            ui.label(tr("range"));
+            ui.add(
+                TextEdit::singleline(&mut self.range_from)
+                    .hint_text(tr("range_from"))
+                    .desired_width(100.0),
+            );
+            ui.label("-");
+            ui.add(
+                TextEdit::singleline(&mut self.range_to)
+                    .hint_text(tr("range_to"))
+                    .desired_width(100.0),
+            );
+            if let Err(e) = &range {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr("mode"));
+            let transcribe = self.job_mode == pipeline::JobMode::Transcribe;
+            egui::ComboBox::from_id_salt("job_mode")
+                .selected_text(if transcribe { tr("mode_transcribe") } else { tr("mode_segments") })
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(transcribe, tr("mode_transcribe")).clicked() {
+                        self.job_mode = pipeline::JobMode::Transcribe;
+                    }
+                    if ui.selectable_label(!transcribe, tr("mode_segments")).clicked() && transcribe {
+                        self.job_mode = pipeline::JobMode::Segments(Default::default());
+                    }
+                });
+            if let pipeline::JobMode::Segments(export) = &mut self.job_mode {
+                egui::ComboBox::from_id_salt("segment_format")
+                    .selected_text(export.format.name())
+                    .show_ui(ui, |ui| {
+                        for format in segments::SegmentFormat::ALL {
+                            ui.selectable_value(&mut export.format, format, format.name());
+                        }
+                    });
+                ui.checkbox(&mut export.write_wav, tr("segments_wav"));
+            }
+        });
+    }
+    fn model_section(&mut self, ui: &mut egui::Ui) {
+        Self::file_selection(ui, &self.file_dialog, &mut self.last_directory, &mut self.models_dir);
+        if self.job_mode == pipeline::JobMode::Transcribe {
+            Self::file_selection(ui, &self.file_dialog, &mut self.last_directory, &mut self.whisper_path);
+            self.model_manager(ui);
+        }
+        ui.horizontal(|ui| {
+            ui.label("VAD");
+            egui::ComboBox::from_id_salt("vad_backend")
+                .selected_text(self.vad_backend.name())
+                .show_ui(ui, |ui| {
+                    for backend in vad::VadBackend::ALL {
+                        ui.selectable_value(&mut self.vad_backend, backend, backend.name());
+                    }
+                });
+            if self.vad_backend.needs_model() {
+                ui.label(tr("sample_rate"));
+                egui::ComboBox::from_id_salt("vad_sample_rate")
+                    .selected_text(format!("{}Hz", self.vad_sample_rate))
+                    .show_ui(ui, |ui| {
+                        for rate in vad::SileroDetector::SUPPORTED_SAMPLE_RATES {
+                            ui.selectable_value(&mut self.vad_sample_rate, rate, format!("{}Hz", rate));
+                        }
+                    });
+            }
+        });
+        if self.vad_backend.needs_model() {
+            Self::file_selection(ui, &self.file_dialog, &mut self.last_directory, &mut self.silero_vad_path);
+            if self.silero_vad_path.path.is_file() {
+                match self.silero_status() {
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.weak(tr("model_checking"));
+                        });
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, trf("model_invalid", &[&e]));
+                    }
+                    Some(Ok(())) => {}
+                }
+            }
+            if !self.models.silero.is_empty() {
+                let mut picked = None;
+                egui::ComboBox::from_id_salt("found_silero_models")
+                    .selected_text(tr("models_pick_silero"))
+                    .show_ui(ui, |ui| {
+                        for path in &self.models.silero {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            if ui.selectable_label(*path == self.silero_vad_path.path, name).clicked() {
+                                picked = Some(path.clone());
+                            }
+                        }
+                    });
+                if let Some(path) = picked {
+                    self.silero_vad_path.set_path(path);
+                }
+            }
+        }
+    }
+    /// 语言、提示词和 whisper 解码参数
+    fn advanced_section(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.centered_and_justified(|ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("language"));
+                    // ui.centered_and_justified(|ui| {
+                    ui.text_edit_singleline(&mut self.language);
+                    // })
+                })
+            });
+            ui.centered_and_justified(|ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("initial_prompt"));
+                    // ui.centered_and_justified(|ui| {
+                    ui.text_edit_singleline(&mut self.initial_prompt);
+                    // })
+                })
+            });
+        });
+        if self.job_mode == pipeline::JobMode::Transcribe {
+            ui.horizontal(|ui| {
+                ui.label(tr("threads"));
+                ui.add(egui::DragValue::new(&mut self.whisper_settings.threads).range(1..=64));
+                ui.label("Beam");
+                ui.add(egui::DragValue::new(&mut self.whisper_settings.beam_size).range(0..=16))
+                    .on_hover_text(tr("beam_hint"));
+                ui.checkbox(&mut self.whisper_settings.translate, tr("translate"));
+            });
+        }
+    }
+    fn output_selection(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(tr("output_button")).clicked() {
+                debug!("开始选择输出文件");
+                self.output_path.default_filename = self
+                    .audio_path
+                    .path
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned();
+                self.output_path.ongoing = true;
+
+                let (extension_name, extension) = match self.job_mode {
+                    pipeline::JobMode::Transcribe => ("SubRip Subtitle", "srt"),
+                    pipeline::JobMode::Segments(export) => {
+                        (export.format.name(), export.format.extension())
+                    }
+                };
+                //真服了，用了refcell 结果告诉我 default filename 是 save file mode 用的
+                let mut dialog = self
+                    .file_dialog
+                    .take()
+                    .title(tr("output_title"))
+                    .default_file_name(&self.output_path.default_filename)
+                    .add_save_extension(extension_name, extension)
+                    .default_save_extension(extension_name);
+                if let Some(directory) = self.last_directory.clone() {
+                    dialog = dialog.initial_directory(directory);
+                }
+                self.file_dialog.replace(dialog);
+                self.file_dialog.borrow_mut().save_file();
+            };
+            if self.output_path.ongoing
+                && let Some(path) = self.file_dialog.borrow_mut().take_picked()
+            {
+                self.last_directory = path.parent().map(Path::to_path_buf);
+                self.output_path.set_path(path.to_path_buf());
+                debug!("保存到{}文件", tr(self.output_path.hint));
+                self.output_path.ongoing = false;
+            } else {
+                if self.output_path.path_string != self.output_path.path.to_string_lossy() {
+                    debug!("检测到文件输入框变更: {}", self.output_path.path_string);
+                    self.output_path.path =
+                        self.output_path.path_string.clone().try_into().unwrap();
+                    debug!(
+                        "当前PathBuf内容 {}",
+                        self.output_path.path.to_string_lossy()
+                    );
+                }
+            }
+            ui.centered_and_justified(|ui| {
+                let file_text_edit = TextEdit::singleline(&mut self.output_path.path_string)
+                    .hint_text(trf("select_hint", &[&tr(self.output_path.hint)]));
+                ui.add(file_text_edit)
+            });
+        });
+    }
+    /// 检查输入是否完整，然后在后台线程中开始任务
+    fn start_button(&mut self, ui: &mut egui::Ui) {
+        let range = audio::TimeRange::parse(&self.range_from, &self.range_to);
+        let ctx = ui.ctx().clone();
+        let should_start = if self.audio_path.path.is_file()
+            && (self.job_mode != pipeline::JobMode::Transcribe
+                || self.current_whisper_info().is_some_and(|info| info.header.is_ok()))
+            && (!self.vad_backend.needs_model() || self.silero_status() == Some(Ok(())))
+            && !self.output_path.path_string.is_empty()
+            && (self.job_mode != pipeline::JobMode::Transcribe || !self.language.is_empty())
+            && self.streams_source == self.audio_path.path
+            && !self.streams_loading
+            && (self.audio_streams.len() <= 1 || !self.selected_streams.is_empty())
+            && range.is_ok()
+            && self.state == AppState::Idle
+        {
+            true
+        } else {
+            false
+        };
+        ui.horizontal(|ui| {
+           let start_text = match self.job_mode {
+               pipeline::JobMode::Transcribe => tr("start_transcribe"),
+               pipeline::JobMode::Segments(_) => tr("start_segments"),
+           };
+           if ui.add_enabled(
+                should_start,
+                Button::new(RichText::new(start_text).size(14.0).strong())
+                    .corner_radius(5.0)
+                    .min_size(egui::vec2(ui.available_width(), 32.8)),
+            ).clicked(){
+               debug!("开始转录");
+               let job = pipeline::Job {
+                   audio_path: self.audio_path.path.clone(),
+                   streams: if self.audio_streams.len() > 1 {
+                       self.selected_streams.clone()
+                   } else {
+                       Vec::new()
+                   },
+                   channel_mode: self.channel_mode,
+                   speakers: self
+                       .speakers
+                       .split([',', '，'])
+                       .map(|label| label.trim().to_string())
+                       .collect(),
+                   range: range.clone().unwrap_or_default(),
+                   mode: self.job_mode,
+                   whisper_path: self.whisper_path.path_string.clone(),
+                   vad_backend: self.vad_backend,
+                   vad_path: self.silero_vad_path.path_string.clone(),
+                   vad_sample_rate: self.vad_sample_rate,
+                   vad_settings: self.vad_settings,
+                   language: self.language.clone(),
+                   initial_prompt: self.initial_prompt.clone(),
+                   whisper_settings: self.whisper_settings,
+                   output_path: self.output_path.path.clone(),
+               };
+               let (sender, receiver) = mpsc::channel();
+               self.events = Some(receiver);
+               self.state = AppState::Resample;
+               self.last_progress = None;
+               self.warnings.clear();
+               self.transcript.clear();
+               self.job_audio.clear();
+               self.timeline.reset();
+               thread::spawn({
+                    let sink = GuiSink {
+                        sender,
+                        ctx: ctx.clone(),
+                    };
+                    move || {
+                        if let Err(e) = pipeline::run_job(&job, &sink) {
+                            error!("转录失败: {}", e);
+                        }
+                    }
+                });
+           };
+        });
+    }
+    /// 当前阶段、进度、警告以及上一次任务的结果
+    fn status(&mut self, ui: &mut egui::Ui) {
+        let state = self.state;
+        let mut stage_text = match state {
+            AppState::Idle => tr("state_idle").to_string(),
+            AppState::VAD => tr("state_vad").to_string(),
+            AppState::Whisper => tr("state_whisper").to_string(),
+            AppState::Resample => tr("state_resample").to_string(),
+            AppState::Saving => tr("state_saving").to_string(),
+            AppState::Finished => tr("state_finished").to_string(),
+        };
+        let running = !matches!(state, AppState::Idle | AppState::Finished);
+        if running && let Some(event) = &self.last_progress {
+            stage_text += &format!(" {:.0}%", event.stage_fraction * 100.0);
+            if let Some((processed, total)) = event.stage_seconds {
+                stage_text += &format!(
+                    " ({} / {})",
+                    progress::format_seconds(processed),
+                    progress::format_seconds(total)
+                );
+            }
+        }
+        ui.label(stage_text);
+        // ui.label("Label");
+        let overall = self.last_progress.map_or(0.0, |event| event.overall_fraction);
+        ui.add(ProgressBar::new(overall).show_percentage());
+        if state != AppState::Idle
+            && let Some(event) = &self.last_progress
+        {
+            ui.label(event.summary());
+        }
+        if let Some(warning) = self.warnings.last() {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                trf("warning", &[&self.warnings.len(), warning]),
+            );
+        }
+        if state == AppState::Idle && !self.finished_outputs.is_empty() {
+            let mut open = None;
+            ui.horizontal_wrapped(|ui| {
+                if self.finished_outputs.iter().any(|output| is_srt(output)) {
+                    ui.label(tr("edit_subtitles"));
+                }
+                for output in self.finished_outputs.iter().filter(|output| is_srt(output)) {
+                    let name = output.file_name().unwrap_or_default().to_string_lossy();
+                    if ui.small_button(name).clicked() {
+                        open = Some(output.clone());
+                    }
+                }
+                if !self.job_audio.is_empty() {
+                    ui.separator();
+                    ui.toggle_value(&mut self.show_timeline, tr("timeline"));
+                }
+            });
+            if let Some(output) = open {
+                self.open_editor(&output);
+            }
+        }
+    }
+    fn transcript_preview(ui: &mut egui::Ui, transcript: &[SubtitleEvent], max_height: f32) {
+        egui::ScrollArea::vertical()
+            .id_salt("transcript_preview")
+            .auto_shrink([false, true])
+            .max_height(max_height)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for subtitle in transcript {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.weak(format!(
+                            "{} -> {}",
+                            progress::format_millis(subtitle.start_ms),
+                            progress::format_millis(subtitle.end_ms)
+                        ));
+                        if let Some(label) = &subtitle.label {
+                            ui.strong(format!("[{}]", label));
+                        }
+                        ui.label(&subtitle.text);
+                    });
+                }
+            });
+    }
     fn file_selection(
         ui: &mut egui::Ui,
         file_dialog: &RefCell<FileDialog>,
+        last_directory: &mut Option<PathBuf>,
         file_selection_data: &mut FileSelectionData,
     ) -> InnerResponse<()> {
         ui.horizontal(|ui| {
             if ui
-                .button("打开".to_string() + &file_selection_data.hint)
+                .button(trf("open_file", &[&tr(file_selection_data.hint)]))
                 .clicked()
             {
-                debug!("开始选择{}文件", file_selection_data.hint);
+                debug!("开始选择{}文件", tr(file_selection_data.hint));
                 file_selection_data.ongoing = true;
 
                 //真服了，用了refcell 结果告诉我 default filename 是 save file mode 用的
-                file_dialog.replace(file_dialog.take().title(&("打开".to_string() + &file_selection_data.hint)));
-                file_dialog.borrow_mut().pick_file();
+                let mut dialog = file_dialog
+                    .take()
+                    .title(&trf("open_file", &[&tr(file_selection_data.hint)]));
+                if let Some(directory) = last_directory.clone() {
+                    dialog = dialog.initial_directory(directory);
+                }
+                file_dialog.replace(dialog);
+                if file_selection_data.directory {
+                    file_dialog.borrow_mut().pick_directory();
+                } else {
+                    file_dialog.borrow_mut().pick_file();
+                }
             };
             //我真服了，之前匹配放前面pathbuf被“偷”走了
             if file_selection_data.ongoing
                 && let Some(path) = file_dialog.borrow_mut().take_picked()
             {
-                file_selection_data.path = path.to_path_buf();
-                file_selection_data.path_string =
-                    file_selection_data.path.to_string_lossy().into_owned();
-                debug!("打开{}文件", file_selection_data.hint);
+                *last_directory = path.parent().map(Path::to_path_buf);
+                file_selection_data.set_path(path.to_path_buf());
+                debug!("打开{}文件", tr(file_selection_data.hint));
                 file_selection_data.ongoing = false;
             } else {
                 if file_selection_data.path_string != file_selection_data.path.to_string_lossy() {
@@ -381,14 +1456,21 @@ impl App {
             }
             ui.centered_and_justified(|ui| {
                 let file_text_edit = TextEdit::singleline(&mut file_selection_data.path_string)
-                    .hint_text("请选择".to_string() + &file_selection_data.hint);
+                    .hint_text(trf("select_hint", &[&tr(file_selection_data.hint)]));
                 ui.add(file_text_edit)
             });
         })
     }
 }
+fn is_srt(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("srt"))
+}
 fn main() -> Result<(), Box<dyn Error>> {
     // let main_start_time = Instant::now();
+    // 要在日志输出到 stdout 之前连上控制台
+    if cli::requested() {
+        cli::attach_console();
+    }
 
     // Determine base log level based on build profile
     let base_log_level = if cfg!(debug_assertions) {
@@ -408,8 +1490,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     let log_spec = format!("{},whisper_rs={}", base_log_level, whisper_level);
 
     Logger::try_with_str(&log_spec)?
-        .log_to_file(FileSpec::default().directory("logs").basename("app"))
-        .create_symlink("logs/latest.log")
+        .log_to_file_and_writer(
+            FileSpec::default().directory(logview::LOG_DIR).basename("app"),
+            Box::new(logview::MemoryWriter),
+        )
+        .create_symlink(Path::new(logview::LOG_DIR).join("latest.log"))
         .duplicate_to_stdout(Duplicate::Trace)
         .format_for_stdout(|w, now, record| {
             let level_style = match record.level() {
@@ -435,10 +1520,18 @@ fn main() -> Result<(), Box<dyn Error>> {
             Cleanup::KeepLogFiles(7),
         )
         .start()?;
+    backend::log_backends();
+    if cli::requested() {
+        // 命令行没有界面语言设置，跟随系统
+        i18n::set_locale(i18n::Locale::system());
+        return cli::run();
+    }
     let native_options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
-            .with_inner_size([725.6, 292.])
-            .with_resizable(false),
+            .with_inner_size([960.0, 640.0])
+            .with_min_inner_size([560.0, 420.0]),
+        // 窗口位置和各个子窗口的布局由 eframe 随设置一起保存
+        persist_window: true,
         ..Default::default()
     };
     eframe::run_native(
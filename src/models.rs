@@ -0,0 +1,375 @@
+use log::warn;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::i18n::{tr, trf};
+
+/// whisper.cpp 官方模型的校验和清单
+const MANIFEST: &str = include_str!("../assets/models.toml");
+
+/// ggml 文件开头的魔数，按小端读出来是 "ggml"
+pub const GGML_MAGIC: u32 = 0x6767_6d6c;
+
+/// 多语言模型的词表大小，只支持英语的 .en 模型少一个
+const MULTILINGUAL_VOCAB: i32 = 51865;
+
+/// 当前 ggml 的量化格式版本，保存在 ftype 的千位上
+const GGML_QNT_VERSION: i32 = 2;
+
+/// vad-rs 使用的 Silero v5 模型的输入输出
+const SILERO_INPUTS: [&str; 3] = ["input", "state", "sr"];
+const SILERO_OUTPUTS: [&str; 2] = ["output", "stateN"];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct KnownModel {
+    pub name: String,
+    pub sha1: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    whisper: Vec<KnownModel>,
+}
+
+pub fn known_models() -> Vec<KnownModel> {
+    match toml::from_str::<Manifest>(MANIFEST) {
+        Ok(manifest) => manifest.whisper,
+        Err(e) => {
+            warn!("Invalid model manifest: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 按 SHA-1 找到对应的官方模型名
+pub fn identify(sha1: &str) -> Option<String> {
+    known_models()
+        .into_iter()
+        .find(|model| model.sha1.eq_ignore_ascii_case(sha1))
+        .map(|model| model.name)
+}
+
+/// whisper ggml 文件头里的超参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhisperHeader {
+    pub n_vocab: i32,
+    pub n_audio_layer: i32,
+    pub n_text_layer: i32,
+    pub n_mels: i32,
+    pub ftype: i32,
+}
+
+impl WhisperHeader {
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut file = fs::File::open(path)?;
+        let mut buffer = [0u8; 4 * 12];
+        file.read_exact(&mut buffer)
+            .map_err(|_| tr("model_too_small"))?;
+        let field = |index: usize| {
+            i32::from_le_bytes(buffer[index * 4..index * 4 + 4].try_into().unwrap())
+        };
+        if field(0) as u32 != GGML_MAGIC {
+            return Err(tr("model_bad_magic").into());
+        }
+        // magic 之后依次是 n_vocab, n_audio_ctx, n_audio_state, n_audio_head, n_audio_layer,
+        // n_text_ctx, n_text_state, n_text_head, n_text_layer, n_mels, ftype
+        let header = Self {
+            n_vocab: field(1),
+            n_audio_layer: field(5),
+            n_text_layer: field(9),
+            n_mels: field(10),
+            ftype: field(11),
+        };
+        header.validate()?;
+        if header.quantization() == "unknown" {
+            warn!(
+                "{}: unknown weight type (ftype {}), leaving it to whisper.cpp",
+                path.to_string_lossy(),
+                header.ftype % 1000
+            );
+        }
+        let version = header.ftype / 1000;
+        if version > GGML_QNT_VERSION {
+            warn!(
+                "{}: quantization version {} is newer than {}, whisper.cpp may not load it",
+                path.to_string_lossy(),
+                version,
+                GGML_QNT_VERSION
+            );
+        }
+        Ok(header)
+    }
+
+    /// 只拒绝明显不是 whisper 模型的文件，权重类型之类交给 whisper.cpp 判断
+    fn validate(&self) -> Result<(), String> {
+        if self.n_vocab <= 0 || !matches!(self.n_mels, 80 | 128) {
+            return Err(tr("model_bad_hparams").to_string());
+        }
+        Ok(())
+    }
+
+    pub fn multilingual(&self) -> bool {
+        self.n_vocab >= MULTILINGUAL_VOCAB
+    }
+
+    pub fn model_type(&self) -> &'static str {
+        match (self.n_audio_layer, self.n_mels, self.n_text_layer) {
+            (4, _, _) => "tiny",
+            (6, _, _) => "base",
+            (12, _, _) => "small",
+            (24, _, _) => "medium",
+            // turbo 的编码器和 large-v3 一样，解码器只有 4 层
+            (32, 128, 4) => "large-v3-turbo",
+            (32, 128, _) => "large-v3",
+            (32, _, _) => "large",
+            _ => "unknown",
+        }
+    }
+
+    /// 权重的量化方式，即 ggml 的 `ggml_ftype`，ftype 里还带着量化版本号(乘以 1000)
+    pub fn quantization(&self) -> &'static str {
+        match self.ftype % 1000 {
+            0 => "f32",
+            1 => "f16",
+            2 => "q4_0",
+            3 => "q4_1",
+            4 => "q4_1_some_f16",
+            7 => "q8_0",
+            8 => "q5_0",
+            9 => "q5_1",
+            10 => "q2_k",
+            11 => "q3_k",
+            12 => "q4_k",
+            13 => "q5_k",
+            14 => "q6_k",
+            15 => "iq2_xxs",
+            16 => "iq2_xs",
+            17 => "iq3_xxs",
+            18 => "iq1_s",
+            19 => "iq4_nl",
+            20 => "iq3_s",
+            21 => "iq2_s",
+            22 => "iq4_xs",
+            23 => "iq1_m",
+            24 => "bf16",
+            _ => "unknown",
+        }
+    }
+}
+
+/// 模型目录中找到的一个 whisper 模型
+#[derive(Debug, Clone)]
+pub struct WhisperModelInfo {
+    pub path: PathBuf,
+    pub file_size: u64,
+    pub header: Result<WhisperHeader, String>,
+}
+
+impl WhisperModelInfo {
+    pub fn inspect(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            file_size: fs::metadata(path).map_or(0, |metadata| metadata.len()),
+            header: WhisperHeader::read(path).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// 文件头读不出来时按文件名里的 `.en` 判断
+    pub fn english_only(&self) -> bool {
+        match &self.header {
+            Ok(header) => !header.multilingual(),
+            Err(_) => self.file_name().contains(".en"),
+        }
+    }
+
+    /// 例如 `large-v3 q5_0 多语言 1.08GB`
+    pub fn summary(&self) -> String {
+        let size = format!("{:.2}GB", self.file_size as f64 / 1024.0 / 1024.0 / 1024.0);
+        match &self.header {
+            Ok(header) => format!(
+                "{} {} {} {}",
+                header.model_type(),
+                header.quantization(),
+                if header.multilingual() {
+                    tr("model_multilingual")
+                } else {
+                    tr("model_english_only")
+                },
+                size
+            ),
+            Err(e) => format!("{} ({})", size, e),
+        }
+    }
+
+    /// 只支持英语的模型配上了其它语言
+    pub fn language_warning(&self, language: &str) -> Option<String> {
+        let english = ["en", "english"].contains(&language.trim().to_ascii_lowercase().as_str());
+        (self.english_only() && !english)
+            .then(|| trf("model_language_warning", &[&self.file_name(), &language]))
+    }
+}
+
+/// 加载 ONNX 模型并检查输入输出是不是 SileroVAD 的
+pub fn validate_silero(path: &Path) -> Result<(), String> {
+    let session = ort::session::Session::builder()
+        .and_then(|builder| builder.commit_from_file(path))
+        .map_err(|e| trf("silero_load_failed", &[&e]))?;
+    let missing: Vec<&str> = SILERO_INPUTS
+        .iter()
+        .filter(|name| !session.inputs.iter().any(|input| input.name == **name))
+        .chain(
+            SILERO_OUTPUTS
+                .iter()
+                .filter(|name| !session.outputs.iter().any(|output| output.name == **name)),
+        )
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(trf("silero_not_v5", &[&missing.join(", ")]));
+    }
+    Ok(())
+}
+
+/// 扫描模型目录的结果
+#[derive(Debug, Clone, Default)]
+pub struct ModelScan {
+    pub whisper: Vec<WhisperModelInfo>,
+    /// SileroVAD 模型(.onnx)
+    pub silero: Vec<PathBuf>,
+}
+
+pub fn scan(directory: &Path) -> ModelScan {
+    let mut result = ModelScan::default();
+    let Ok(entries) = fs::read_dir(directory) else {
+        return result;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("bin") => result.whisper.push(WhisperModelInfo::inspect(&path)),
+            Some("onnx") => result.silero.push(path),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// 计算文件的 SHA-1，大模型要几秒钟，应该在后台线程里调用
+pub fn sha1_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = sha1_smol::Sha1::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.digest().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每个测试一个临时目录，结束时删掉
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("whisper_models_{}_{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// 写一个只有文件头的 ggml 模型
+    fn write_header(dir: &TempDir, name: &str, hparams: [i32; 11]) -> PathBuf {
+        let path = dir.0.join(format!("{}.bin", name));
+        let mut bytes = GGML_MAGIC.to_le_bytes().to_vec();
+        for value in hparams {
+            bytes.extend(value.to_le_bytes());
+        }
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    /// n_vocab, n_audio_ctx, n_audio_state, n_audio_head, n_audio_layer,
+    /// n_text_ctx, n_text_state, n_text_head, n_text_layer, n_mels, ftype
+    fn hparams(vocab: i32, audio_layer: i32, text_layer: i32, mels: i32, ftype: i32) -> [i32; 11] {
+        [vocab, 1500, 1280, 20, audio_layer, 448, 1280, 20, text_layer, mels, ftype]
+    }
+
+    #[test]
+    fn reads_large_v3_turbo() {
+        let dir = TempDir::new("turbo");
+        let path = write_header(&dir, "turbo", hparams(51866, 32, 4, 128, 2008));
+        let header = WhisperHeader::read(&path).unwrap();
+        assert_eq!(header.model_type(), "large-v3-turbo");
+        assert_eq!(header.quantization(), "q5_0");
+        assert!(header.multilingual());
+
+        let path = write_header(&dir, "large_v3", hparams(51866, 32, 32, 128, 1));
+        let header = WhisperHeader::read(&path).unwrap();
+        assert_eq!(header.model_type(), "large-v3");
+        assert_eq!(header.quantization(), "f16");
+    }
+
+    #[test]
+    fn maps_ggml_ftypes() {
+        let dir = TempDir::new("ftypes");
+        for (ftype, name) in [(4, "q4_1_some_f16"), (12, "q4_k"), (13, "q5_k"), (14, "q6_k")] {
+            let path = write_header(&dir, name, hparams(51864, 4, 4, 80, 2000 + ftype));
+            let header = WhisperHeader::read(&path).unwrap();
+            assert_eq!(header.quantization(), name);
+            assert_eq!(header.model_type(), "tiny");
+            assert!(!header.multilingual());
+        }
+    }
+
+    #[test]
+    fn unknown_ftype_is_not_rejected() {
+        let dir = TempDir::new("unknown_ftype");
+        let path = write_header(&dir, "unknown_ftype", hparams(51865, 6, 6, 80, 3099));
+        let header = WhisperHeader::read(&path).unwrap();
+        assert_eq!(header.quantization(), "unknown");
+    }
+
+    #[test]
+    fn rejects_non_whisper_files() {
+        let dir = TempDir::new("bad");
+        let path = dir.0.join("bad_magic.bin");
+        fs::write(&path, [0u8; 48]).unwrap();
+        assert!(WhisperHeader::read(&path).is_err());
+
+        let path = write_header(&dir, "bad_mels", hparams(51865, 6, 6, 40, 1));
+        assert!(WhisperHeader::read(&path).is_err());
+    }
+}
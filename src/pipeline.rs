@@ -0,0 +1,451 @@
+use crate::progress::{
+    ProgressSink, RESAMPLE_WEIGHT, Reporter, SAVING_WEIGHT, VAD_SHARE, format_seconds,
+};
+use crate::i18n::trf;
+use crate::{audio, models, segments, transcribe, vad};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+pub enum AppState {
+    Idle,
+    Resample,
+    VAD,
+    Whisper,
+    Saving,
+    Finished,
+}
+impl Default for AppState {
+    fn default() -> Self {
+        Self::Idle
+    }
+}
+
+/// 任务要产出什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobMode {
+    /// 完整转录，输出 SRT 字幕
+    #[default]
+    Transcribe,
+    /// 只做 VAD 并导出语音段，不需要 whisper 模型
+    Segments(segments::SegmentExport),
+}
+
+/// 一次转录任务需要的全部参数，GUI 和命令行共用。
+/// 也可以写成 TOML/JSON 任务文件，每个输出旁边都会保存一份实际使用的参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Job {
+    pub audio_path: PathBuf,
+    /// 要转录的音频流，为空时使用 ffmpeg 选出的最佳音频流；
+    /// 选了多条时每条流单独输出一个字幕文件
+    pub streams: Vec<usize>,
+    pub channel_mode: audio::ChannelMode,
+    /// `ChannelMode::Split` 时每个声道的标签，缺省为 CH1、CH2...
+    pub speakers: Vec<String>,
+    /// 只转录这一段，字幕时间仍然是相对于原文件开头的
+    pub range: audio::TimeRange,
+    pub mode: JobMode,
+    pub whisper_path: String,
+    pub vad_backend: vad::VadBackend,
+    pub vad_path: String,
+    /// SileroVAD 模型运行的采样率。VAD 的分帧和时间戳始终按 whisper 的 16kHz 计算，
+    /// 选 8000 时只是把送进模型的数据降采样
+    pub vad_sample_rate: u32,
+    pub vad_settings: vad::VadSettings,
+    pub language: String,
+    pub initial_prompt: String,
+    pub whisper_settings: transcribe::WhisperSettings,
+    pub output_path: PathBuf,
+}
+
+impl Default for Job {
+    fn default() -> Self {
+        Self {
+            audio_path: PathBuf::new(),
+            streams: Vec::new(),
+            channel_mode: audio::ChannelMode::default(),
+            speakers: Vec::new(),
+            range: audio::TimeRange::default(),
+            mode: JobMode::default(),
+            whisper_path: String::new(),
+            vad_backend: vad::VadBackend::default(),
+            vad_path: String::new(),
+            vad_sample_rate: 16000,
+            vad_settings: vad::VadSettings::default(),
+            language: "zh".to_string(),
+            initial_prompt: String::new(),
+            whisper_settings: transcribe::WhisperSettings::default(),
+            output_path: PathBuf::new(),
+        }
+    }
+}
+
+impl Job {
+    /// 读取任务文件，扩展名为 `.json` 时按 JSON 解析，其余按 TOML。
+    /// 文件里的相对路径相对于任务文件所在的目录
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read job file {}: {}", path.to_string_lossy(), e))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let mut job: Job = if is_json {
+            serde_json::from_str(&text)?
+        } else {
+            toml::from_str(&text)?
+        };
+        let base = path.parent().unwrap_or(Path::new(""));
+        job.map_paths(|path| base.join(path));
+        Ok(job)
+    }
+
+    /// 对所有非空的相对路径调用 `f`
+    fn map_paths(&mut self, f: impl Fn(&Path) -> PathBuf) {
+        let map = |path: &Path| {
+            if path.as_os_str().is_empty() || path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                f(path)
+            }
+        };
+        self.audio_path = map(&self.audio_path);
+        self.output_path = map(&self.output_path);
+        self.whisper_path = map(Path::new(&self.whisper_path)).to_string_lossy().into_owned();
+        self.vad_path = map(Path::new(&self.vad_path)).to_string_lossy().into_owned();
+    }
+
+    /// 写成 TOML 任务文件
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let text = format!(
+            "# WhisperGuiNeo {}\n{}",
+            env!("CARGO_PKG_VERSION"),
+            toml::to_string_pretty(self)?
+        );
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// 开始之前检查参数和模型文件
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        self.range.validate()?;
+        self.vad_settings.validate()?;
+        // 不然要等整个文件解码、重采样完，创建 SileroDetector 时才会失败
+        if self.vad_backend.needs_model()
+            && !vad::SileroDetector::SUPPORTED_SAMPLE_RATES.contains(&self.vad_sample_rate)
+        {
+            return Err(trf("vad_sample_rate_unsupported", &[&self.vad_sample_rate]).into());
+        }
+        self.validate_models()
+    }
+
+    /// 选错模型文件时给出明确的错误而不是在加载时崩溃
+    pub fn validate_models(&self) -> Result<(), Box<dyn Error>> {
+        if self.mode == JobMode::Transcribe {
+            models::WhisperHeader::read(Path::new(&self.whisper_path))
+                .map_err(|e| format!("Whisper model {}: {}", self.whisper_path, e))?;
+        }
+        if self.vad_backend.needs_model() {
+            models::validate_silero(Path::new(&self.vad_path))
+                .map_err(|e| format!("SileroVAD model {}: {}", self.vad_path, e))?;
+        }
+        Ok(())
+    }
+
+    /// 只生成 `output_path` 这一个输出的任务，保存在输出旁边用来重新生成它。
+    /// 路径都换成绝对路径，读取时才不会被当成相对于任务文件的路径
+    fn for_output(&self, stream_index: Option<usize>, output_path: &Path) -> Job {
+        let mut job = self.clone();
+        job.streams = stream_index.into_iter().collect();
+        job.output_path = output_path.to_path_buf();
+        job.map_paths(|path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+        job
+    }
+
+    pub fn speaker_label(&self, channel: usize) -> String {
+        self.speakers
+            .get(channel)
+            .filter(|label| !label.is_empty())
+            .cloned()
+            .unwrap_or_else(|| format!("CH{}", channel + 1))
+    }
+
+    /// 每条音频流对应的输出文件
+    pub fn outputs(&self) -> Vec<(Option<usize>, PathBuf)> {
+        let output_path = match self.mode {
+            JobMode::Transcribe => self.output_path.clone(),
+            JobMode::Segments(export) => self.output_path.with_extension(export.format.extension()),
+        };
+        match self.streams.as_slice() {
+            [] => vec![(None, output_path)],
+            [index] => vec![(Some(*index), output_path)],
+            streams => streams
+                .iter()
+                .map(|index| {
+                    (
+                        Some(*index),
+                        with_suffix(&output_path, &format!("stream{}", index)),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `a/b.srt` + `stream1` -> `a/b.stream1.srt`
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_name = format!("{}.{}", stem, suffix);
+    if let Some(extension) = path.extension() {
+        file_name += ".";
+        file_name += &extension.to_string_lossy();
+    }
+    path.with_file_name(file_name)
+}
+
+/// 运行整个任务，运行过程通过 `sink` 报告；失败时也会发出 `JobEvent::Error`
+pub fn run_job(job: &Job, sink: &dyn ProgressSink) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut reporter = Reporter::new(sink);
+    let result = job
+        .validate()
+        .and_then(|()| transcribe_outputs(job, &mut reporter));
+    match &result {
+        Ok(outputs) => {
+            let summary = reporter.finish(outputs.clone());
+            info!(
+                "Finished {} output(s) in {}: {} of audio, real-time factor {:.2}",
+                outputs.len(),
+                format_seconds(summary.elapsed.as_secs_f64()),
+                format_seconds(summary.audio_seconds),
+                summary.real_time_factor.unwrap_or_default()
+            );
+        }
+        Err(e) => reporter.error(e.to_string()),
+    }
+    result
+}
+
+fn transcribe_outputs(
+    job: &Job,
+    reporter: &mut Reporter<'_>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let outputs = job.outputs();
+    let mut written = Vec::new();
+    // 只做 VAD 时没有 whisper 阶段，VAD 占满整个声道
+    let vad_share = match job.mode {
+        JobMode::Transcribe => VAD_SHARE,
+        JobMode::Segments(_) => 1.0,
+    };
+    for (idx, (stream_index, output_path)) in outputs.iter().enumerate() {
+        reporter.begin_stream(idx, outputs.len());
+        // begin_stage 的 start 和 weight 是这个阶段在当前音频流中的位置
+        reporter.begin_stage(AppState::Resample, 0.0, RESAMPLE_WEIGHT);
+        let sample_rate = transcribe::WHISPER_SAMPLE_RATE;
+        let tracks = audio::do_resample(
+            reporter,
+            sample_rate,
+            &job.audio_path,
+            *stream_index,
+            job.channel_mode,
+            job.range,
+        )?;
+        let duration = tracks.first().map_or(0, |track| track.len()) as f64 / sample_rate as f64;
+        reporter.set_audio_duration(duration);
+
+        // 每个声道在这条音频流中占的比重
+        let split = job.channel_mode == audio::ChannelMode::Split;
+        let track_weight = (1.0 - RESAMPLE_WEIGHT - SAVING_WEIGHT) / tracks.len().max(1) as f32;
+        let mut track_subs = Vec::new();
+        let mut track_segments = Vec::new();
+        for (channel, mut resampled) in tracks.into_iter().enumerate() {
+            let track_start = RESAMPLE_WEIGHT + channel as f32 * track_weight;
+            let label = split.then(|| job.speaker_label(channel));
+            reporter.set_label(label.clone());
+            reporter.begin_stage(AppState::VAD, track_start, track_weight * vad_share);
+            let settings = job.vad_settings;
+            let mut vad_output = match job.vad_backend {
+                vad::VadBackend::Silero => {
+                    let mut detector =
+                        vad::SileroDetector::new(&job.vad_path, sample_rate, job.vad_sample_rate)?;
+                    vad::do_vad(reporter, sample_rate, settings, &mut detector, &mut resampled)?
+                }
+                vad::VadBackend::Energy => {
+                    let mut detector = vad::EnergyDetector::new();
+                    vad::do_vad(reporter, sample_rate, settings, &mut detector, &mut resampled)?
+                }
+            };
+            let mut active = std::mem::take(&mut vad_output.speeches);
+            for speech in &mut active {
+                speech.offset_by(job.range.start() as f32);
+            }
+            if let JobMode::Segments(export) = job.mode {
+                if export.write_wav {
+                    segments::write_wavs(
+                        &segments::wav_directory(output_path),
+                        label.as_deref(),
+                        sample_rate,
+                        job.range.start(),
+                        &resampled,
+                        &active,
+                    )?;
+                }
+                track_segments.extend(segments::Segment::from_speeches(label, &active));
+            }
+            reporter.audio(
+                output_path.clone(),
+                job.range.start(),
+                sample_rate,
+                Arc::new(resampled),
+                &vad_output,
+            );
+            if job.mode != JobMode::Transcribe {
+                continue;
+            }
+
+            reporter.begin_stage(
+                AppState::Whisper,
+                track_start + track_weight * VAD_SHARE,
+                track_weight * (1.0 - VAD_SHARE),
+            );
+            let subs = transcribe::do_whisper(
+                reporter,
+                &job.whisper_path,
+                &active,
+                &job.language,
+                &job.initial_prompt,
+                job.whisper_settings,
+            )?;
+            track_subs.push((job.speaker_label(channel), subs));
+        }
+        reporter.begin_stage(AppState::Saving, 1.0 - SAVING_WEIGHT, SAVING_WEIGHT);
+        match job.mode {
+            JobMode::Transcribe => {
+                let subs = if split {
+                    transcribe::merge_labelled(track_subs)
+                } else {
+                    track_subs.pop().map(|(_, subs)| subs).unwrap_or_else(srtlib::Subtitles::new)
+                };
+                subs.write_to_file(output_path, None)?;
+            }
+            JobMode::Segments(export) => {
+                segments::write_segments(output_path, export.format, track_segments)?;
+            }
+        }
+        info!("Saved to {}", output_path.to_string_lossy());
+        let job_path = output_path.with_extension("job.toml");
+        job.for_output(*stream_index, output_path).save(&job_path)?;
+        info!("Job saved to {}", job_path.to_string_lossy());
+        written.push(output_path.clone());
+        if let JobMode::Segments(export) = job.mode
+            && export.write_wav
+        {
+            written.push(segments::wav_directory(output_path));
+        }
+        reporter.finish_stream();
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每个测试一个临时目录，结束时删掉
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("whisper_job_{}_{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn relative_paths_follow_the_job_file() {
+        let dir = TempDir::new("relative");
+        let vad_path = dir.0.join("silero_vad.onnx");
+        let path = dir.0.join("a.job.toml");
+        let text = format!(
+            "audio_path = \"in.mp4\"\noutput_path = \"out/a.srt\"\n\
+             whisper_path = \"models/ggml-base.bin\"\nvad_path = {:?}\n",
+            vad_path.to_string_lossy()
+        );
+        fs::write(&path, text).unwrap();
+        let job = Job::load(&path).unwrap();
+        assert_eq!(job.audio_path, dir.0.join("in.mp4"));
+        assert_eq!(job.output_path, dir.0.join("out/a.srt"));
+        assert_eq!(
+            Path::new(&job.whisper_path),
+            dir.0.join("models/ggml-base.bin")
+        );
+        assert_eq!(Path::new(&job.vad_path), vad_path);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let dir = TempDir::new("unknown");
+        for (name, text) in [
+            ("top.toml", "audio_path = \"a.mp4\"\nbeam = 5\n"),
+            ("nested.toml", "[vad_settings]\nthreshhold = 0.3\n"),
+            ("nested.json", "{\"whisper_settings\": {\"beams\": 5}}"),
+        ] {
+            let path = dir.0.join(name);
+            fs::write(&path, text).unwrap();
+            assert!(Job::load(&path).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn saved_jobs_round_trip() {
+        let dir = TempDir::new("round_trip");
+        let job = Job {
+            audio_path: dir.0.join("in.mkv"),
+            streams: vec![1, 2],
+            speakers: vec!["A".to_string(), "B".to_string()],
+            range: audio::TimeRange { from: Some(1.5), to: Some(60.0) },
+            mode: JobMode::Segments(segments::SegmentExport {
+                format: segments::SegmentFormat::Csv,
+                write_wav: true,
+            }),
+            vad_backend: vad::VadBackend::Energy,
+            vad_sample_rate: 8000,
+            initial_prompt: "提示".to_string(),
+            output_path: dir.0.join("out.srt"),
+            ..Default::default()
+        };
+        let output = dir.0.join("out.stream2.csv");
+        let saved = job.for_output(Some(2), &output);
+        assert_eq!(saved.streams, [2]);
+
+        let toml_path = dir.0.join("out.job.toml");
+        saved.save(&toml_path).unwrap();
+        assert_eq!(Job::load(&toml_path).unwrap(), saved);
+
+        let json_path = dir.0.join("out.job.json");
+        fs::write(&json_path, serde_json::to_string(&saved).unwrap()).unwrap();
+        assert_eq!(Job::load(&json_path).unwrap(), saved);
+    }
+
+    #[test]
+    fn unsupported_vad_sample_rate_is_rejected() {
+        let job = Job {
+            vad_backend: vad::VadBackend::Silero,
+            vad_sample_rate: 44100,
+            ..Default::default()
+        };
+        assert!(job.validate().is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use rodio::buffer::SamplesBuffer;
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+use std::error::Error;
+
+/// 播放内存中的单声道采样，同一时间只播放一段
+pub struct Player {
+    // OutputStream 被 drop 后就没有声音了，必须一直持有
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sink: Option<Sink>,
+}
+
+impl Player {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let (stream, handle) = OutputStream::try_default()?;
+        Ok(Self {
+            _stream: stream,
+            handle,
+            sink: None,
+        })
+    }
+
+    /// 停止正在播放的内容并开始播放 `samples`
+    pub fn play(&mut self, sample_rate: u32, samples: Vec<f32>) -> Result<(), Box<dyn Error>> {
+        self.stop();
+        let sink = Sink::try_new(&self.handle)?;
+        sink.append(SamplesBuffer::new(1, sample_rate, samples));
+        self.sink = Some(sink);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.sink.as_ref().is_some_and(|sink| !sink.empty())
+    }
+}
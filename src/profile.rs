@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{pipeline, transcribe, vad};
+
+/// 配置文件保存在程序所在目录下的这个目录里，每个配置一个 `<名字>.toml`
+const PROFILE_DIR: &str = "profiles";
+
+/// 一组可以命名保存的设置，例如“快速草稿”用小模型、“定稿”用 large-v3 加 beam search。
+/// 只包含和具体输入文件无关的设置；手写的配置可以只写其中一部分，没写的保持不变
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whisper_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vad_backend: Option<vad::VadBackend>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vad_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vad_sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vad: Option<vad::VadSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whisper: Option<transcribe::WhisperSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<pipeline::JobMode>,
+}
+
+impl Profile {
+    /// 用这个配置中写了的参数覆盖任务中对应的参数
+    pub fn apply_to(&self, job: &mut pipeline::Job) {
+        if let Some(path) = &self.whisper_path {
+            job.whisper_path = path.to_string_lossy().into_owned();
+        }
+        if let Some(backend) = self.vad_backend {
+            job.vad_backend = backend;
+        }
+        if let Some(path) = &self.vad_path {
+            job.vad_path = path.to_string_lossy().into_owned();
+        }
+        if let Some(sample_rate) = self.vad_sample_rate {
+            job.vad_sample_rate = sample_rate;
+        }
+        if let Some(settings) = self.vad {
+            job.vad_settings = settings;
+        }
+        if let Some(settings) = self.whisper {
+            job.whisper_settings = settings;
+        }
+        if let Some(language) = &self.language {
+            job.language = language.clone();
+        }
+        if let Some(prompt) = &self.initial_prompt {
+            job.initial_prompt = prompt.clone();
+        }
+        if let Some(mode) = self.mode {
+            job.mode = mode;
+        }
+    }
+}
+
+/// 程序所在目录下的 profiles，不随启动时的工作目录变化
+fn profile_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(PROFILE_DIR)))
+        .unwrap_or_else(|| PathBuf::from(PROFILE_DIR))
+}
+
+fn stored_path(name: &str) -> PathBuf {
+    profile_dir().join(format!("{}.toml", name))
+}
+
+/// 按名字排序的所有配置
+pub fn list_profiles() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(profile_dir()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// 读取 profiles 目录下名字为 `name` 的配置
+pub fn load(name: &str) -> Result<Profile, Box<dyn Error>> {
+    validate_name(name)?;
+    read(&stored_path(name))
+}
+
+/// 命令行用：`name` 是一个存在的文件时直接读取它，否则按名字读取 profiles 目录下的配置
+pub fn load_file_or_name(name: &str) -> Result<Profile, Box<dyn Error>> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return read(path);
+    }
+    load(name)
+}
+
+/// 配置里的相对路径相对于配置文件所在的目录
+fn read(path: &Path) -> Result<Profile, Box<dyn Error>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("cannot read profile {}: {}", path.to_string_lossy(), e))?;
+    let mut profile: Profile = toml::from_str(&text)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    for model_path in [&mut profile.whisper_path, &mut profile.vad_path]
+        .into_iter()
+        .flatten()
+    {
+        if !model_path.as_os_str().is_empty() && model_path.is_relative() {
+            *model_path = base.join(&*model_path);
+        }
+    }
+    Ok(profile)
+}
+
+pub fn save(name: &str, profile: &Profile) -> Result<(), Box<dyn Error>> {
+    validate_name(name)?;
+    fs::create_dir_all(profile_dir())?;
+    fs::write(stored_path(name), toml::to_string_pretty(profile)?)?;
+    Ok(())
+}
+
+pub fn delete(name: &str) -> Result<(), Box<dyn Error>> {
+    validate_name(name)?;
+    fs::remove_file(stored_path(name))?;
+    Ok(())
+}
+
+/// 名字会直接用作文件名
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("profile name is empty".to_string());
+    }
+    if name.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|']) {
+        return Err(format!("invalid profile name: {}", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_model_paths_follow_the_profile_file() {
+        let dir = std::env::temp_dir().join(format!("whisper_profile_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let absolute = dir.join("silero_vad.onnx");
+        let path = dir.join("draft.toml");
+        let profile = Profile {
+            whisper_path: Some(PathBuf::from("models/ggml-large-v3.bin")),
+            vad_path: Some(absolute.clone()),
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        fs::write(&path, toml::to_string_pretty(&profile).unwrap()).unwrap();
+
+        let loaded = load_file_or_name(&path.to_string_lossy());
+        fs::remove_dir_all(&dir).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.whisper_path, Some(dir.join("models/ggml-large-v3.bin")));
+        assert_eq!(loaded.vad_path, Some(absolute));
+        assert_eq!(loaded.language.as_deref(), Some("en"));
+        assert_eq!(loaded.mode, None);
+    }
+
+    #[test]
+    fn only_present_fields_are_applied() {
+        let mut job = pipeline::Job {
+            language: "zh".to_string(),
+            initial_prompt: "prompt".to_string(),
+            ..Default::default()
+        };
+        let profile: Profile = toml::from_str("language = \"en\"").unwrap();
+        profile.apply_to(&mut job);
+        assert_eq!(job.language, "en");
+        assert_eq!(job.initial_prompt, "prompt");
+    }
+
+    #[test]
+    fn names_cannot_leave_the_profile_directory() {
+        assert!(load("../draft").is_err());
+        assert!(toml::from_str::<Profile>("unknown = 1").is_err());
+    }
+}
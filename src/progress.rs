@@ -0,0 +1,331 @@
+use crate::i18n::trf;
+use crate::pipeline::AppState;
+use crate::vad::VadOutput;
+use std::path::PathBuf;
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
+
+/// 整个任务中各阶段所占的比重，按一条音频流为 1.0 计
+pub const RESAMPLE_WEIGHT: f32 = 0.1;
+pub const SAVING_WEIGHT: f32 = 0.05;
+/// 每个声道中 VAD 所占的比例，其余是 whisper
+pub const VAD_SHARE: f32 = 0.15;
+
+/// 两次进度事件之间的最小间隔，避免每解码一帧就发一次
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 任务运行过程中发出的事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobEvent {
+    /// 进入新的阶段
+    Stage(AppState),
+    Progress(ProgressEvent),
+    /// 不影响继续运行的问题，例如 VAD 强制切分了过长的语音段
+    Warning(String),
+    /// whisper 刚转录出的一条字幕
+    Subtitle(SubtitleEvent),
+    /// 一路音频完成 VAD 后的重采样结果和逐帧语音概率，供字幕编辑器和时间轴使用
+    Audio(AudioEvent),
+    /// 任务失败，之后不会再有其它事件
+    Error(String),
+    /// 任务完成，`outputs` 是写出的所有文件
+    Finished {
+        outputs: Vec<PathBuf>,
+        summary: ProgressEvent,
+    },
+}
+
+/// 某一时刻整个任务的进度快照
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressEvent {
+    pub stage: AppState,
+    pub stage_fraction: f32,
+    /// 当前阶段已处理的音频时长和总时长(秒)，无法按时间计算的阶段为 `None`
+    pub stage_seconds: Option<(f64, f64)>,
+    /// 按各阶段比重加权后的总进度
+    pub overall_fraction: f32,
+    pub elapsed: Duration,
+    pub eta: Option<Duration>,
+    /// 已经处理过的输入音频时长(秒)
+    pub audio_seconds: f64,
+    /// 处理耗时 / 音频时长，小于 1 表示比实时快
+    pub real_time_factor: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleEvent {
+    /// 分声道转录时的声道/说话人标签
+    pub label: Option<String>,
+    /// 相对于原文件开头的时间(毫秒)
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioEvent {
+    /// 这路音频属于哪个输出文件
+    pub output: PathBuf,
+    /// 分声道转录时的声道/说话人标签
+    pub label: Option<String>,
+    /// 第一个采样在原文件中的时间(秒)
+    pub start: f64,
+    pub sample_rate: u32,
+    pub samples: Arc<Vec<f32>>,
+    /// VAD 每一帧的语音概率，检测器出错的帧为 NaN
+    pub probabilities: Arc<Vec<f32>>,
+    /// VAD 每一帧的采样数
+    pub chunk_size: usize,
+}
+
+/// 接收任务事件的一方，GUI、命令行或者库的使用者各自实现
+pub trait ProgressSink {
+    fn emit(&self, event: JobEvent);
+
+    /// 是否需要 `JobEvent::Audio`，它带着整段重采样后的音频，不用时别让它一直占着内存
+    fn wants_audio(&self) -> bool {
+        false
+    }
+}
+
+impl ProgressSink for mpsc::Sender<JobEvent> {
+    fn emit(&self, event: JobEvent) {
+        // 接收方已经不在了就没人关心进度了
+        let _ = self.send(event);
+    }
+}
+
+/// 丢弃所有事件
+impl ProgressSink for () {
+    fn emit(&self, _event: JobEvent) {}
+}
+
+/// 各阶段通过它报告进度，它负责换算成整个任务的进度并发给 `ProgressSink`
+pub struct Reporter<'a> {
+    sink: &'a dyn ProgressSink,
+    stage: AppState,
+    /// 当前阶段的进度，0.0..=1.0
+    fraction: f32,
+    seconds: Option<(f64, f64)>,
+    /// 当前阶段在整个任务中的起点和比重
+    stage_start: f32,
+    stage_weight: f32,
+    /// 当前音频流在整个任务中的起点和比重
+    stream_start: f32,
+    stream_weight: f32,
+    started: Instant,
+    last_emit: Option<Instant>,
+    /// 已经处理完的音频流的总时长，以及当前音频流的时长(秒)
+    audio_done: f64,
+    audio_current: f64,
+    label: Option<String>,
+}
+
+impl<'a> Reporter<'a> {
+    pub fn new(sink: &'a dyn ProgressSink) -> Self {
+        Self {
+            sink,
+            stage: AppState::Idle,
+            fraction: 0.0,
+            seconds: None,
+            stage_start: 0.0,
+            stage_weight: 1.0,
+            stream_start: 0.0,
+            stream_weight: 1.0,
+            started: Instant::now(),
+            last_emit: None,
+            audio_done: 0.0,
+            audio_current: 0.0,
+            label: None,
+        }
+    }
+
+    pub fn set(&mut self, fraction: f32) {
+        self.fraction = fraction.clamp(0.0, 1.0);
+        self.emit_progress(false);
+    }
+
+    /// 按音频时间更新进度
+    pub fn set_seconds(&mut self, processed: f64, total: f64) {
+        let processed = processed.clamp(0.0, total);
+        self.seconds = Some((processed, total));
+        if total > 0.0 {
+            self.fraction = (processed / total) as f32;
+        }
+        self.emit_progress(false);
+    }
+
+    pub fn warn(&self, message: impl Into<String>) {
+        self.sink.emit(JobEvent::Warning(message.into()));
+    }
+
+    pub fn subtitle(&self, start_ms: i64, end_ms: i64, text: &str) {
+        self.sink.emit(JobEvent::Subtitle(SubtitleEvent {
+            label: self.label.clone(),
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+        }));
+    }
+
+    /// 把重采样后的音频和 VAD 结果交给界面，`ProgressSink::wants_audio` 为 false 时什么都不做
+    pub fn audio(
+        &self,
+        output: PathBuf,
+        start: f64,
+        sample_rate: u32,
+        samples: Arc<Vec<f32>>,
+        vad: &VadOutput,
+    ) {
+        if !self.sink.wants_audio() {
+            return;
+        }
+        self.sink.emit(JobEvent::Audio(AudioEvent {
+            output,
+            label: self.label.clone(),
+            start,
+            sample_rate,
+            samples,
+            probabilities: Arc::new(vad.probabilities.clone()),
+            chunk_size: vad.chunk_size,
+        }));
+    }
+
+    pub fn error(&self, message: impl Into<String>) {
+        self.sink.emit(JobEvent::Error(message.into()));
+    }
+
+    /// 之后产生的字幕事件都带上这个标签
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+
+    /// 开始处理第 `index` 条(共 `count` 条)音频流
+    pub fn begin_stream(&mut self, index: usize, count: usize) {
+        self.stream_weight = 1.0 / count.max(1) as f32;
+        self.stream_start = index as f32 * self.stream_weight;
+        self.audio_current = 0.0;
+    }
+
+    pub fn finish_stream(&mut self) {
+        self.audio_done += self.audio_current;
+        self.audio_current = 0.0;
+    }
+
+    /// 当前音频流的时长，重采样结束后才能确定
+    pub fn set_audio_duration(&mut self, seconds: f64) {
+        self.audio_current = seconds;
+    }
+
+    /// 进入新的阶段，`start` 和 `weight` 是这个阶段在当前音频流中的位置
+    pub fn begin_stage(&mut self, stage: AppState, start: f32, weight: f32) {
+        self.stage = stage;
+        self.stage_start = self.stream_start + start * self.stream_weight;
+        self.stage_weight = weight * self.stream_weight;
+        self.fraction = 0.0;
+        self.seconds = None;
+        self.sink.emit(JobEvent::Stage(stage));
+        self.emit_progress(true);
+    }
+
+    /// 任务完成，发出 `Finished` 事件并返回最终的进度
+    pub fn finish(&mut self, outputs: Vec<PathBuf>) -> ProgressEvent {
+        self.stage = AppState::Finished;
+        self.stage_start = 1.0;
+        self.stage_weight = 0.0;
+        self.fraction = 1.0;
+        self.seconds = None;
+        let summary = self.snapshot();
+        self.sink.emit(JobEvent::Finished { outputs, summary });
+        summary
+    }
+
+    fn emit_progress(&mut self, force: bool) {
+        let now = Instant::now();
+        if !force
+            && self
+                .last_emit
+                .is_some_and(|last| now.duration_since(last) < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_emit = Some(now);
+        self.sink.emit(JobEvent::Progress(self.snapshot()));
+    }
+
+    pub fn snapshot(&self) -> ProgressEvent {
+        let elapsed = self.started.elapsed();
+        let overall = (self.stage_start + self.stage_weight * self.fraction).clamp(0.0, 1.0);
+        let eta = (overall > 0.0 && overall < 1.0)
+            .then(|| elapsed.mul_f64((1.0 - overall as f64) / overall as f64));
+
+        // 重采样阶段还不知道音频总长，用已解码的时长代替
+        let audio_current = match (self.stage, self.seconds) {
+            (AppState::Resample, Some((_, total))) => total,
+            _ => self.audio_current,
+        };
+        let stream_fraction = if self.stream_weight > 0.0 {
+            ((overall - self.stream_start) / self.stream_weight).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let audio_seconds = self.audio_done + audio_current * stream_fraction as f64;
+        let real_time_factor =
+            (audio_seconds > 0.0).then(|| elapsed.as_secs_f64() / audio_seconds);
+
+        ProgressEvent {
+            stage: self.stage,
+            stage_fraction: self.fraction,
+            stage_seconds: self.seconds,
+            overall_fraction: overall,
+            elapsed,
+            eta,
+            audio_seconds,
+            real_time_factor,
+        }
+    }
+}
+
+impl ProgressEvent {
+    /// 一行文字的摘要，界面和命令行共用
+    pub fn summary(&self) -> String {
+        let mut text = trf(
+            "progress_elapsed",
+            &[
+                &format!("{:.1}", self.overall_fraction * 100.0),
+                &format_seconds(self.elapsed.as_secs_f64()),
+            ],
+        );
+        if let Some(eta) = self.eta {
+            text += &trf("progress_eta", &[&format_seconds(eta.as_secs_f64())]);
+        }
+        if let Some(rtf) = self.real_time_factor {
+            text += &trf(
+                "progress_audio",
+                &[&format_seconds(self.audio_seconds), &format!("{:.2}", rtf)],
+            );
+        }
+        text
+    }
+}
+
+/// 把秒数格式化成 `hh:mm:ss`
+pub fn format_seconds(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// 把毫秒格式化成 `hh:mm:ss.mmm`
+pub fn format_millis(millis: i64) -> String {
+    let millis = millis.max(0);
+    format!(
+        "{}.{:03}",
+        format_seconds((millis / 1000) as f64),
+        millis % 1000
+    )
+}
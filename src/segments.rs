@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::i18n::tr;
+use crate::vad::ActiveSpeech;
+
+/// 只做 VAD 时语音段的导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum SegmentFormat {
+    #[default]
+    Json,
+    Csv,
+    /// Audacity 的标签轨道，可以直接 导入 -> 标签
+    Audacity,
+}
+
+impl SegmentFormat {
+    pub const ALL: [SegmentFormat; 3] = [
+        SegmentFormat::Json,
+        SegmentFormat::Csv,
+        SegmentFormat::Audacity,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SegmentFormat::Json => "JSON",
+            SegmentFormat::Csv => "CSV",
+            SegmentFormat::Audacity => tr("segments_audacity"),
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            SegmentFormat::Json => "json",
+            SegmentFormat::Csv => "csv",
+            SegmentFormat::Audacity => "audacity",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SegmentFormat::Json => "json",
+            SegmentFormat::Csv => "csv",
+            SegmentFormat::Audacity => "txt",
+        }
+    }
+}
+
+impl fmt::Display for SegmentFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for SegmentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.id().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown segment format: {} (expected json, csv or audacity)", s))
+    }
+}
+
+impl From<SegmentFormat> for String {
+    fn from(format: SegmentFormat) -> Self {
+        format.id().to_string()
+    }
+}
+
+impl TryFrom<String> for SegmentFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// 只做 VAD 时的导出设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SegmentExport {
+    pub format: SegmentFormat,
+    /// 同时把每个语音段写成单独的 WAV 文件
+    pub write_wav: bool,
+}
+
+/// 导出的一个语音段，时间是相对于原文件开头的秒数
+#[derive(Debug, Clone, Serialize)]
+pub struct Segment {
+    pub index: usize,
+    /// 分声道时的声道/说话人标签
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Segment {
+    pub fn from_speeches(label: Option<String>, speeches: &[ActiveSpeech]) -> Vec<Segment> {
+        speeches
+            .iter()
+            .map(|speech| Segment {
+                index: 0,
+                label: label.clone(),
+                start: speech.start_time,
+                end: speech.end_time,
+            })
+            .collect()
+    }
+
+    /// Audacity 标签的文字，制表符和换行会破坏标签文件的格式
+    fn name(&self) -> String {
+        let name = match &self.label {
+            Some(label) => format!("{} {}", label, self.index),
+            None => format!("speech {}", self.index),
+        };
+        name.replace(['\t', '\r', '\n'], " ")
+    }
+}
+
+/// 含有逗号、引号或换行的字段加上引号，引号写两遍
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// 用户输入的标签会出现在文件名里，把路径分隔符和 Windows 不允许的字符换掉
+fn file_label(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// 按开始时间排序、编号后写出
+pub fn write_segments(
+    path: &Path,
+    format: SegmentFormat,
+    mut segments: Vec<Segment>,
+) -> Result<(), Box<dyn Error>> {
+    segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+    for (idx, segment) in segments.iter_mut().enumerate() {
+        segment.index = idx + 1;
+    }
+    let mut file = fs::File::create(path)?;
+    match format {
+        SegmentFormat::Json => serde_json::to_writer_pretty(&mut file, &segments)?,
+        SegmentFormat::Csv => {
+            writeln!(file, "index,label,start,end,duration")?;
+            for segment in &segments {
+                writeln!(
+                    file,
+                    "{},{},{:.3},{:.3},{:.3}",
+                    segment.index,
+                    csv_field(segment.label.as_deref().unwrap_or_default()),
+                    segment.start,
+                    segment.end,
+                    segment.end - segment.start
+                )?;
+            }
+        }
+        SegmentFormat::Audacity => {
+            for segment in &segments {
+                writeln!(file, "{:.6}\t{:.6}\t{}", segment.start, segment.end, segment.name())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `a/b.json` -> `a/b_segments/`
+pub fn wav_directory(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_segments", stem))
+}
+
+/// 把 `samples` 中每个语音段写成 16bit 单声道 WAV，`start` 是 `samples` 第一个采样在原文件中的时间
+pub fn write_wavs(
+    directory: &Path,
+    label: Option<&str>,
+    sample_rate: u32,
+    start: f64,
+    samples: &[f32],
+    speeches: &[ActiveSpeech],
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(directory)?;
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let to_index = |seconds: f32| {
+        (((seconds as f64 - start) * sample_rate as f64).max(0.0) as usize).min(samples.len())
+    };
+    for speech in speeches {
+        let name = format!(
+            "{}{:010.3}-{:010.3}.wav",
+            label.map(|label| format!("{}_", file_label(label))).unwrap_or_default(),
+            speech.start_time,
+            speech.end_time
+        );
+        let mut writer = hound::WavWriter::create(directory.join(name), spec)?;
+        for sample in &samples[to_index(speech.start_time)..to_index(speech.end_time)] {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每个测试一个临时目录，结束时删掉
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("whisper_segments_{}_{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment { index: 0, label: Some("Bob, \"B\"".to_string()), start: 3.0, end: 4.5 },
+            Segment { index: 0, label: Some("Alice".to_string()), start: 1.0, end: 2.25 },
+        ]
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let dir = TempDir::new("json");
+        let path = dir.0.join("a.json");
+        write_segments(&path, SegmentFormat::Json, segments()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let items = value.as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["index"], 1);
+        assert_eq!(items[0]["label"], "Alice");
+        assert_eq!(items[0]["start"], 1.0);
+        assert_eq!(items[1]["label"], "Bob, \"B\"");
+        assert_eq!(items[1]["end"], 4.5);
+    }
+
+    #[test]
+    fn csv_quotes_labels() {
+        let dir = TempDir::new("csv");
+        let path = dir.0.join("a.csv");
+        write_segments(&path, SegmentFormat::Csv, segments()).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "index,label,start,end,duration",
+                "1,Alice,1.000,2.250,1.250",
+                "2,\"Bob, \"\"B\"\"\",3.000,4.500,1.500",
+            ]
+        );
+    }
+
+    #[test]
+    fn audacity_labels_are_tab_separated() {
+        let dir = TempDir::new("audacity");
+        let path = dir.0.join("a.txt");
+        let mut segments = segments();
+        segments[0].label = Some("tab\there".to_string());
+        write_segments(&path, SegmentFormat::Audacity, segments).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Vec<&str>> = text.lines().map(|line| line.split('\t').collect()).collect();
+        assert_eq!(
+            lines,
+            [
+                vec!["1.000000", "2.250000", "Alice 1"],
+                vec!["3.000000", "4.500000", "tab here 2"],
+            ]
+        );
+    }
+
+    #[test]
+    fn wavs_have_the_segment_length() {
+        let dir = TempDir::new("wav");
+        let samples = vec![0.25; 16000 * 3];
+        let speeches = [
+            ActiveSpeech::new(10.5, 11.0, 11.0, Vec::new()),
+            ActiveSpeech::new(11.5, 12.5, 12.5, Vec::new()),
+        ];
+        // samples 从原文件的第 10 秒开始
+        write_wavs(&dir.0, Some("../a/b:c"), 16000, 10.0, &samples, &speeches).unwrap();
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        let name = files[0].file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(name, ".._a_b_c_000010.500-000011.000.wav");
+        let lengths: Vec<u32> = files
+            .iter()
+            .map(|file| hound::WavReader::open(file).unwrap().duration())
+            .collect();
+        assert_eq!(lengths, [8000, 16000]);
+        let mut reader = hound::WavReader::open(&files[0]).unwrap();
+        let first: i16 = reader.samples().next().unwrap().unwrap();
+        assert_eq!(first, (0.25 * i16::MAX as f32) as i16);
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{audio, i18n, pipeline, theme, transcribe, vad};
+
+/// 图形界面在两次启动之间记住的设置，通过 eframe 的持久化存储保存。
+/// 音频流的选择和时间范围只对当前文件有意义，不保存
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio_path: PathBuf,
+    pub whisper_path: PathBuf,
+    pub silero_vad_path: PathBuf,
+    pub output_path: PathBuf,
+    /// 扫描模型的目录
+    pub models_dir: PathBuf,
+    /// 上一次在文件对话框中选择的文件所在的目录
+    pub last_directory: Option<PathBuf>,
+    pub language: String,
+    pub initial_prompt: String,
+    pub vad_backend: vad::VadBackend,
+    pub vad_sample_rate: u32,
+    pub vad_settings: vad::VadSettings,
+    pub whisper_settings: transcribe::WhisperSettings,
+    pub channel_mode: audio::ChannelMode,
+    pub speakers: String,
+    pub job_mode: pipeline::JobMode,
+    pub show_timeline: bool,
+    pub show_log: bool,
+    /// 上一次选择的配置名
+    pub profile: String,
+    /// 界面语言，`None` 表示跟随系统
+    pub ui_language: Option<i18n::Locale>,
+    pub theme: theme::Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            audio_path: PathBuf::new(),
+            whisper_path: PathBuf::new(),
+            silero_vad_path: PathBuf::new(),
+            output_path: PathBuf::new(),
+            models_dir: PathBuf::new(),
+            last_directory: None,
+            language: "zh".to_string(),
+            initial_prompt: String::new(),
+            vad_backend: vad::VadBackend::default(),
+            vad_sample_rate: 16000,
+            vad_settings: vad::VadSettings::default(),
+            whisper_settings: transcribe::WhisperSettings::default(),
+            channel_mode: audio::ChannelMode::default(),
+            speakers: String::new(),
+            job_mode: pipeline::JobMode::default(),
+            show_timeline: false,
+            show_log: false,
+            profile: String::new(),
+            ui_language: None,
+            theme: theme::Theme::default(),
+        }
+    }
+}
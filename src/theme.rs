@@ -0,0 +1,105 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::i18n::tr;
+
+/// 界面主题
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Theme {
+    /// 跟随系统的深色/浅色设置
+    #[default]
+    System,
+    Light,
+    Dark,
+    Latte,
+    Frappe,
+    Macchiato,
+    Mocha,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 7] = [
+        Theme::System,
+        Theme::Light,
+        Theme::Dark,
+        Theme::Latte,
+        Theme::Frappe,
+        Theme::Macchiato,
+        Theme::Mocha,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::Latte => "latte",
+            Theme::Frappe => "frappe",
+            Theme::Macchiato => "macchiato",
+            Theme::Mocha => "mocha",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::System => tr("theme_system"),
+            Theme::Light => tr("theme_light"),
+            Theme::Dark => tr("theme_dark"),
+            Theme::Latte => "Catppuccin Latte",
+            Theme::Frappe => "Catppuccin Frappé",
+            Theme::Macchiato => "Catppuccin Macchiato",
+            Theme::Mocha => "Catppuccin Mocha",
+        }
+    }
+
+    fn flavour(&self) -> Option<catppuccin_egui::Theme> {
+        match self {
+            Theme::Latte => Some(catppuccin_egui::LATTE),
+            Theme::Frappe => Some(catppuccin_egui::FRAPPE),
+            Theme::Macchiato => Some(catppuccin_egui::MACCHIATO),
+            Theme::Mocha => Some(catppuccin_egui::MOCHA),
+            _ => None,
+        }
+    }
+
+    /// 修改 `ctx` 的样式，只需要在主题变化时调用一次
+    pub fn apply(&self, ctx: &egui::Context) {
+        // 先恢复 egui 默认的配色，catppuccin 是在当前配色的基础上修改的
+        ctx.set_visuals_of(egui::Theme::Dark, egui::Visuals::dark());
+        ctx.set_visuals_of(egui::Theme::Light, egui::Visuals::light());
+        let preference = match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Light | Theme::Latte => egui::ThemePreference::Light,
+            _ => egui::ThemePreference::Dark,
+        };
+        ctx.set_theme(preference);
+        if let Some(flavour) = self.flavour() {
+            catppuccin_egui::set_theme(ctx, flavour);
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl From<Theme> for String {
+    fn from(theme: Theme) -> Self {
+        theme.id().to_string()
+    }
+}
+
+impl TryFrom<String> for Theme {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|theme| theme.id().eq_ignore_ascii_case(&s))
+            .ok_or_else(|| format!("unknown theme: {}", s))
+    }
+}
@@ -0,0 +1,285 @@
+use eframe::egui;
+use eframe::egui::{Align2, DragValue, FontId, Pos2, Rect, Sense, Shape, Stroke, pos2, vec2};
+
+use crate::i18n::{tr, trf};
+use crate::progress::{AudioEvent, format_millis};
+use crate::vad::{self, ActiveSpeech, VadSettings};
+
+/// 波形按 10ms 一块预先算好最小/最大值，缩小显示时不用遍历所有采样
+const PEAK_BLOCKS_PER_SECOND: usize = 100;
+const PLOT_HEIGHT: f32 = 160.0;
+
+/// 显示波形、VAD 概率曲线、阈值和分段结果的时间轴
+pub struct Timeline {
+    /// 当前显示的是第几路音频
+    track: usize,
+    /// 可见范围(秒，相对于这一路音频的开头)
+    view_start: f64,
+    view_len: f64,
+    /// `peaks` 和 `spans` 是用哪一路音频、哪组参数算出来的
+    peaks_for: Option<usize>,
+    spans_for: Option<(usize, VadSettings)>,
+    peaks: Vec<(f32, f32)>,
+    spans: Vec<ActiveSpeech>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            track: 0,
+            view_start: 0.0,
+            view_len: 30.0,
+            peaks_for: None,
+            spans_for: None,
+            peaks: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+}
+
+impl Timeline {
+    /// 换了一批音频(例如重新转录)后调用
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, tracks: &[AudioEvent], settings: &mut VadSettings) {
+        if tracks.is_empty() {
+            ui.label(tr("timeline_empty"));
+            return;
+        }
+        self.track = self.track.min(tracks.len() - 1);
+        if tracks.len() > 1 {
+            let track_name = |audio: &AudioEvent| {
+                let mut name = audio
+                    .output
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                if let Some(label) = &audio.label {
+                    name += &format!(" [{}]", label);
+                }
+                name
+            };
+            egui::ComboBox::from_id_salt("timeline_track")
+                .selected_text(track_name(&tracks[self.track]))
+                .show_ui(ui, |ui| {
+                    for (idx, audio) in tracks.iter().enumerate() {
+                        ui.selectable_value(&mut self.track, idx, track_name(audio));
+                    }
+                });
+        }
+        let audio = &tracks[self.track];
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label(tr("vad_threshold"));
+            ui.add(
+                DragValue::new(&mut settings.threshold)
+                    .range(VadSettings::THRESHOLD)
+                    .speed(0.01),
+            );
+            ui.label(tr("vad_min_silence"));
+            ui.add(seconds_drag(&mut settings.min_silence, VadSettings::MIN_SILENCE));
+            ui.label(tr("vad_min_speech"));
+            ui.add(seconds_drag(&mut settings.min_speech, VadSettings::MIN_SPEECH));
+            ui.label(tr("vad_max_speech"));
+            ui.add(seconds_drag(&mut settings.max_speech, VadSettings::MAX_SPEECH));
+            ui.label(tr("vad_slice"));
+            ui.add(seconds_drag(&mut settings.slice, VadSettings::SLICE));
+            if ui.button(tr("vad_reset")).clicked() {
+                *settings = VadSettings::default();
+            }
+        });
+        if let Err(e) = settings.validate() {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+
+        if self.peaks_for != Some(self.track) {
+            self.peaks = compute_peaks(audio);
+            self.peaks_for = Some(self.track);
+        }
+        if self.spans_for != Some((self.track, *settings)) {
+            self.spans = vad::resegment(
+                audio.sample_rate,
+                *settings,
+                audio.chunk_size,
+                &audio.probabilities,
+            );
+            self.spans_for = Some((self.track, *settings));
+        }
+        ui.weak(trf("timeline_help", &[&self.spans.len()]));
+
+        let total = audio.samples.len() as f64 / audio.sample_rate as f64;
+        let (rect, response) = ui.allocate_exact_size(
+            vec2(ui.available_width(), PLOT_HEIGHT),
+            Sense::click_and_drag(),
+        );
+
+        // 平移和缩放
+        if response.dragged() {
+            self.view_start -= response.drag_delta().x as f64 / rect.width() as f64 * self.view_len;
+        }
+        if response.hovered() {
+            let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+            if scroll != 0.0
+                && let Some(pointer) = response.hover_pos()
+            {
+                let anchor = self.time_at(rect, pointer.x);
+                self.view_len *= (-scroll as f64 * 0.005).exp();
+                self.view_len = self.view_len.clamp(1.0, total.max(1.0));
+                self.view_start =
+                    anchor - (pointer.x - rect.left()) as f64 / rect.width() as f64 * self.view_len;
+            }
+        }
+        self.view_len = self.view_len.clamp(1.0, total.max(1.0));
+        self.view_start = self.view_start.clamp(0.0, (total - self.view_len).max(0.0));
+
+        let painter = ui.painter_at(rect);
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+        // 语音段：实心部分是真正的语音，浅色部分是尾部静音和补零
+        let speech_fill = visuals.selection.bg_fill.gamma_multiply(0.5);
+        let padding_fill = visuals.selection.bg_fill.gamma_multiply(0.2);
+        for span in &self.spans {
+            let (start, end, data_end) = (
+                span.start_time as f64,
+                span.end_time as f64,
+                span.data_end_time as f64,
+            );
+            if data_end < self.view_start || start > self.view_start + self.view_len {
+                continue;
+            }
+            let x = |time: f64| self.x_at(rect, time);
+            painter.rect_filled(
+                Rect::from_x_y_ranges(x(start)..=x(end), rect.y_range()),
+                0.0,
+                speech_fill,
+            );
+            painter.rect_filled(
+                Rect::from_x_y_ranges(x(end)..=x(data_end), rect.y_range()),
+                0.0,
+                padding_fill,
+            );
+            painter.vline(x(start), rect.y_range(), Stroke::new(1.0, visuals.selection.stroke.color));
+        }
+
+        // 波形
+        let wave_stroke = Stroke::new(1.0, visuals.weak_text_color());
+        let center = rect.center().y;
+        let half = rect.height() / 2.0;
+        for column in 0..rect.width() as usize {
+            let (from, to) = self.column_range(rect, column, PEAK_BLOCKS_PER_SECOND as f64);
+            let (min, max) = self.peaks[from.min(self.peaks.len())..to.min(self.peaks.len())]
+                .iter()
+                .fold((0.0f32, 0.0f32), |(min, max), (lo, hi)| (min.min(*lo), max.max(*hi)));
+            if max > min {
+                let x = rect.left() + column as f32 + 0.5;
+                painter.vline(x, (center - max * half)..=(center - min * half), wave_stroke);
+            }
+        }
+
+        // 概率曲线，出错的帧断开
+        let chunks_per_second = audio.sample_rate as f64 / audio.chunk_size.max(1) as f64;
+        let prob_y = |prob: f32| rect.bottom() - prob * rect.height();
+        let curve_stroke = Stroke::new(1.5, visuals.hyperlink_color);
+        let mut line: Vec<Pos2> = Vec::new();
+        for column in 0..rect.width() as usize {
+            let (from, to) = self.column_range(rect, column, chunks_per_second);
+            let probs = &audio.probabilities[from.min(audio.probabilities.len())
+                ..to.min(audio.probabilities.len())];
+            let prob = probs
+                .iter()
+                .filter(|prob| !prob.is_nan())
+                .fold(None, |max: Option<f32>, prob| Some(max.map_or(*prob, |max| max.max(*prob))));
+            match prob {
+                Some(prob) if !probs.iter().any(|prob| prob.is_nan()) => {
+                    line.push(pos2(rect.left() + column as f32 + 0.5, prob_y(prob)));
+                }
+                _ => {
+                    if line.len() > 1 {
+                        painter.add(Shape::line(std::mem::take(&mut line), curve_stroke));
+                    }
+                    line.clear();
+                }
+            }
+        }
+        if line.len() > 1 {
+            painter.add(Shape::line(line, curve_stroke));
+        }
+
+        // 阈值线
+        let threshold_y = prob_y(settings.threshold);
+        painter.extend(Shape::dashed_line(
+            &[pos2(rect.left(), threshold_y), pos2(rect.right(), threshold_y)],
+            Stroke::new(1.0, visuals.warn_fg_color),
+            6.0,
+            4.0,
+        ));
+
+        // 可见范围的起止时间
+        let font = FontId::monospace(10.0);
+        let absolute_ms = |time: f64| ((audio.start + time) * 1000.0) as i64;
+        painter.text(
+            rect.left_bottom() + vec2(2.0, -2.0),
+            Align2::LEFT_BOTTOM,
+            format_millis(absolute_ms(self.view_start)),
+            font.clone(),
+            visuals.text_color(),
+        );
+        painter.text(
+            rect.right_bottom() + vec2(-2.0, -2.0),
+            Align2::RIGHT_BOTTOM,
+            format_millis(absolute_ms(self.view_start + self.view_len)),
+            font,
+            visuals.text_color(),
+        );
+
+        if let Some(pointer) = response.hover_pos() {
+            painter.vline(pointer.x, rect.y_range(), Stroke::new(1.0, visuals.text_color()));
+            let time = self.time_at(rect, pointer.x);
+            let chunk = (time * chunks_per_second) as usize;
+            let prob = audio.probabilities.get(chunk).copied().unwrap_or(f32::NAN);
+            response.on_hover_text(trf(
+                "timeline_probability",
+                &[&format_millis(absolute_ms(time)), &format!("{:.2}", prob)],
+            ));
+        }
+    }
+
+    fn time_at(&self, rect: Rect, x: f32) -> f64 {
+        self.view_start + (x - rect.left()) as f64 / rect.width() as f64 * self.view_len
+    }
+
+    fn x_at(&self, rect: Rect, time: f64) -> f32 {
+        let x = rect.left() + ((time - self.view_start) / self.view_len) as f32 * rect.width();
+        x.clamp(rect.left(), rect.right())
+    }
+
+    /// 第 `column` 列像素覆盖的元素下标范围，`per_second` 是每秒多少个元素，至少包含一个
+    fn column_range(&self, rect: Rect, column: usize, per_second: f64) -> (usize, usize) {
+        let seconds_per_column = self.view_len / rect.width() as f64;
+        let start = self.view_start + column as f64 * seconds_per_column;
+        let from = (start * per_second) as usize;
+        let to = (((start + seconds_per_column) * per_second).ceil() as usize).max(from + 1);
+        (from, to)
+    }
+}
+
+fn seconds_drag(value: &mut f32, range: std::ops::RangeInclusive<f32>) -> DragValue<'_> {
+    DragValue::new(value).range(range).speed(0.05).suffix("s")
+}
+
+fn compute_peaks(audio: &AudioEvent) -> Vec<(f32, f32)> {
+    let block = (audio.sample_rate as usize / PEAK_BLOCKS_PER_SECOND).max(1);
+    audio
+        .samples
+        .chunks(block)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .fold((0.0f32, 0.0f32), |(min, max), sample| (min.min(*sample), max.max(*sample)))
+        })
+        .collect()
+}
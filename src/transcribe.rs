@@ -1,19 +1,45 @@
 use log::info;
+use serde::{Deserialize, Serialize};
 use srtlib::{Subtitle, Subtitles, Timestamp};
 use std::error::Error;
-use std::sync::{Arc, Mutex};
 use whisper_rs::{
     DtwModelPreset, FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters,
 };
 
+use crate::progress::Reporter;
 use crate::vad::ActiveSpeech;
 
+/// whisper 只接受 16kHz 单声道输入
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// 除语言和提示词以外的 whisper 参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WhisperSettings {
+    pub threads: u32,
+    /// 0 表示贪心解码，大于 0 时使用 beam search
+    pub beam_size: u32,
+    /// 翻译成英文而不是原语言转录
+    pub translate: bool,
+}
+
+impl Default for WhisperSettings {
+    fn default() -> Self {
+        Self {
+            threads: 8,
+            beam_size: 0,
+            translate: false,
+        }
+    }
+}
+
 pub fn do_whisper(
-    progress:Arc<Mutex<f32>>,
+    reporter: &mut Reporter<'_>,
     model_path: &str,
     active_speech_list: &[ActiveSpeech],
     language: &str,
     initial_prompt_text: &str,
+    settings: WhisperSettings,
 ) -> Result<Subtitles, Box<dyn Error>> {
     // Install a hook to log any errors from the whisper C++ code.
     whisper_rs::install_logging_hooks();
@@ -25,20 +51,28 @@ pub fn do_whisper(
         model_preset: DtwModelPreset::LargeV3Turbo,
     };
 
-    let ctx =
-        WhisperContext::new_with_params(model_path, context_param).expect("failed to load model");
+    let ctx = WhisperContext::new_with_params(model_path, context_param)
+        .map_err(|e| format!("failed to load model {}: {}", model_path, e))?;
     // Create a state
-    let mut state = ctx.create_state().expect("failed to create key");
+    let mut state = ctx
+        .create_state()
+        .map_err(|e| format!("failed to create whisper state: {}", e))?;
 
     // Create a params object for running the model.
     // The number of past samples to consider defaults to 0.
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 0 });
+    let strategy = if settings.beam_size > 0 {
+        SamplingStrategy::BeamSearch {
+            beam_size: settings.beam_size as i32,
+            patience: -1.0,
+        }
+    } else {
+        SamplingStrategy::Greedy { best_of: 0 }
+    };
+    let mut params = FullParams::new(strategy);
 
     // Edit params as needed.
-    // Set the number of threads to use to 1.
-    params.set_n_threads(8);
-    // Enable translation.
-    params.set_translate(false);
+    params.set_n_threads(settings.threads.max(1) as i32);
+    params.set_translate(settings.translate);
     // Set the language
     params.set_language(Some(language));
     // Disable anything that prints to stdout.
@@ -60,19 +94,21 @@ pub fn do_whisper(
     for (idx,active_speech) in active_speech_list.iter().enumerate() {
         let s = active_speech.data.to_vec();
         // s.extend(vec![0.0; 16000usize]);
-        state.full(params.clone(), &s).expect("failed to run model");
+        state
+            .full(params.clone(), &s)
+            .map_err(|e| format!("failed to run model: {}", e))?;
 
         // Create a file to write the transcript to.
 
         // Iterate through the segments of the transcript.
         let num_segments = state
             .full_n_segments()
-            .expect("failed to get number of segments");
+            .map_err(|e| format!("failed to get number of segments: {}", e))?;
         for i in 0..num_segments {
             // Get the transcribed text and timestamps for the current segment.
             let segment_text_raw = state
                 .full_get_segment_text(i)
-                .expect("failed to get segment");
+                .map_err(|e| format!("failed to get segment: {}", e))?;
 
             let mut processed_text_slice = segment_text_raw.as_str();
 
@@ -97,17 +133,20 @@ pub fn do_whisper(
 
             let start_timestamp = state
                 .full_get_segment_t0(i)
-                .expect("failed to get start timestamp");
+                .map_err(|e| format!("failed to get start timestamp: {}", e))?;
             let end_timestamp = state
                 .full_get_segment_t1(i)
-                .expect("failed to get end timestamp");
-            let start_time_ms = start_timestamp * 10 + ((active_speech.start_time * 1000.0) as i64);
-            let mut end_time_ms = end_timestamp * 10 + ((active_speech.start_time * 1000.0) as i64);
-            if end_time_ms > (active_speech.end_time * 1000.0) as i64 {
-                end_time_ms = (active_speech.end_time * 1000.0) as i64;
-            }
+                .map_err(|e| format!("failed to get end timestamp: {}", e))?;
+            // whisper 的时间戳可能落在尾部静音或补零里，统一截到真正的语音结束处
+            let speech_end_ms = (active_speech.end_time * 1000.0) as i64;
+            let start_time_ms = (start_timestamp * 10
+                + ((active_speech.start_time * 1000.0) as i64))
+                .min(speech_end_ms);
+            let end_time_ms = (end_timestamp * 10 + ((active_speech.start_time * 1000.0) as i64))
+                .clamp(start_time_ms, speech_end_ms);
 
             info!("[{}] -> [{}]: {}", start_time_ms, end_time_ms, segment);
+            reporter.subtitle(start_time_ms, end_time_ms, &segment);
             let start_timestamp = Timestamp::from_milliseconds(start_time_ms as u32);
             let end_timestamp = Timestamp::from_milliseconds(end_time_ms as u32);
 
@@ -115,9 +154,25 @@ pub fn do_whisper(
             subs.push(Subtitle::new(num, start_timestamp, end_timestamp, segment));
             num += 1;
         }
-        *progress.lock().unwrap() = idx as f32 / total as f32;
+        reporter.set((idx + 1) as f32 / total as f32);
     }
     let et = std::time::Instant::now();
     info!("took {}ms", (et - st).as_millis());
     Ok(subs)
 }
+
+/// 把多路(例如分声道)的字幕合并成一个，每条字幕前加上所属声道/说话人的标签
+pub fn merge_labelled(tracks: Vec<(String, Subtitles)>) -> Subtitles {
+    let mut merged: Vec<Subtitle> = Vec::new();
+    for (label, subs) in tracks {
+        for mut sub in subs.to_vec() {
+            sub.text = format!("[{}] {}", label, sub.text);
+            merged.push(sub);
+        }
+    }
+    merged.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+    for (idx, sub) in merged.iter_mut().enumerate() {
+        sub.num = idx + 1;
+    }
+    Subtitles::new_from_vec(merged)
+}
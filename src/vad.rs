@@ -1,138 +1,871 @@
 use std::error::Error;
-use std::sync::{Arc, Mutex};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 use log::{error, warn};
-use vad_rs::{Vad, VadStatus};
+use serde::{Deserialize, Serialize};
+use vad_rs::Vad;
+
+use crate::i18n::{tr, trf};
+use crate::progress::Reporter;
 
 
 #[derive(Debug, Clone)]
 pub struct ActiveSpeech {
     pub start_time: f32,
+    /// 最后一帧语音结束的时间，不包含尾部静音
     pub end_time: f32,
+    /// `data` 覆盖到的时间，包含尾部静音和补零
+    pub data_end_time: f32,
     pub data: Vec<f32>,
 }
 
 impl ActiveSpeech {
-    pub fn new(start_time: f32, end_time: f32, data: Vec<f32>) -> Self {
+    pub fn new(start_time: f32, end_time: f32, data_end_time: f32, data: Vec<f32>) -> Self {
         Self {
             start_time,
             end_time,
+            data_end_time,
             data,
         }
     }
+
+    /// 把时间平移 `seconds`，用于只处理了文件的一部分时换算回原文件中的时间
+    pub fn offset_by(&mut self, seconds: f32) {
+        self.start_time += seconds;
+        self.end_time += seconds;
+        self.data_end_time += seconds;
+    }
+}
+
+/// 逐帧给出语音概率的检测器，`do_vad` 只关心这个概率
+pub trait VoiceDetector {
+    /// 返回这一帧是语音的概率，范围 0.0..=1.0
+    fn compute(&mut self, chunk: &[f32]) -> Result<f32, Box<dyn Error>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum VadBackend {
+    #[default]
+    Silero,
+    Energy,
+}
+
+impl VadBackend {
+    pub const ALL: [VadBackend; 2] = [VadBackend::Silero, VadBackend::Energy];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VadBackend::Silero => "SileroVAD",
+            VadBackend::Energy => tr("vad_energy"),
+        }
+    }
+
+    /// 命令行和配置文件里使用的名字
+    pub fn id(&self) -> &'static str {
+        match self {
+            VadBackend::Silero => "silero",
+            VadBackend::Energy => "energy",
+        }
+    }
+
+    pub fn needs_model(&self) -> bool {
+        matches!(self, VadBackend::Silero)
+    }
+}
+
+impl FromStr for VadBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.id().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown VAD backend: {} (expected silero or energy)", s))
+    }
+}
+
+impl From<VadBackend> for String {
+    fn from(backend: VadBackend) -> Self {
+        backend.id().to_string()
+    }
 }
 
-pub fn do_vad(
-    progress: Arc<Mutex<f32>>,
+impl TryFrom<String> for VadBackend {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+pub struct SileroDetector {
+    vad: Vad,
+    /// 输入采样率与模型采样率之比
+    decimation: usize,
+    buffer: Vec<f32>,
+}
+
+impl SileroDetector {
+    /// Silero 只支持 8000 和 16000 两种采样率
+    pub const SUPPORTED_SAMPLE_RATES: [u32; 2] = [16000, 8000];
+
+    /// `input_sample_rate` 是送进来的音频的采样率，`model_sample_rate` 是模型实际运行的采样率，
+    /// 前者必须是后者的整数倍。只有送进模型的数据会被降采样，
+    /// 分帧和时间戳仍然按 `input_sample_rate` 计算
+    pub fn new(
+        model_path: &str,
+        input_sample_rate: u32,
+        model_sample_rate: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        if !Self::SUPPORTED_SAMPLE_RATES.contains(&model_sample_rate) {
+            return Err(format!("SileroVAD does not support {}Hz", model_sample_rate).into());
+        }
+        if !input_sample_rate.is_multiple_of(model_sample_rate) {
+            return Err(format!(
+                "Input sample rate {}Hz is not a multiple of {}Hz",
+                input_sample_rate, model_sample_rate
+            )
+            .into());
+        }
+        let vad = Vad::new(model_path, model_sample_rate.try_into().unwrap())?;
+        Ok(Self {
+            vad,
+            decimation: (input_sample_rate / model_sample_rate) as usize,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl VoiceDetector for SileroDetector {
+    fn compute(&mut self, chunk: &[f32]) -> Result<f32, Box<dyn Error>> {
+        let chunk = if self.decimation > 1 {
+            // 简单的均值降采样，对 VAD 来说足够了
+            self.buffer.clear();
+            self.buffer.extend(
+                chunk
+                    .chunks(self.decimation)
+                    .map(|s| s.iter().sum::<f32>() / s.len() as f32),
+            );
+            self.buffer.as_slice()
+        } else {
+            chunk
+        };
+        // do_vad 保证每一帧都是完整的，这里出错就是真的出错了，交给调用者处理
+        Ok(self.vad.compute(chunk)?.prob)
+    }
+}
+
+/// 基于 RMS 能量和自适应噪声底的内置检测器，不需要任何模型文件
+pub struct EnergyDetector {
+    noise_floor_db: f32,
+    /// 高出噪声底多少 dB 时概率为 0.5
+    margin_db: f32,
+    /// 低于这个电平一律视为静音
+    min_level_db: f32,
+}
+
+impl Default for EnergyDetector {
+    fn default() -> Self {
+        Self {
+            noise_floor_db: -50.0,
+            margin_db: 10.0,
+            min_level_db: -60.0,
+        }
+    }
+}
+
+impl EnergyDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl VoiceDetector for EnergyDetector {
+    fn compute(&mut self, chunk: &[f32]) -> Result<f32, Box<dyn Error>> {
+        if chunk.is_empty() {
+            return Ok(0.0);
+        }
+        let rms = (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt();
+        let level_db = 20.0 * (rms + 1e-10).log10();
+
+        // 噪声底下降得快、上升得慢，这样持续的说话不会被当成噪声吸收掉
+        if level_db < self.noise_floor_db {
+            self.noise_floor_db = 0.5 * self.noise_floor_db + 0.5 * level_db;
+        } else {
+            self.noise_floor_db = 0.995 * self.noise_floor_db + 0.005 * level_db;
+        }
+        // 数字静音(补零)约 -200dB，不限制的话噪声底要很久才能爬回来，之后的底噪都会被当成语音
+        self.noise_floor_db = self.noise_floor_db.max(self.min_level_db);
+
+        if level_db < self.min_level_db {
+            return Ok(0.0);
+        }
+        let snr = level_db - self.noise_floor_db;
+        Ok(1.0 / (1.0 + (-(snr - self.margin_db) / 2.0).exp()))
+    }
+}
+
+/// 分段参数，时间都以秒为单位
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VadSettings {
+    /// 语音概率高于这个值的帧视为语音
+    pub threshold: f32,
+    /// 静音持续这么久才结束一个语音段
+    pub min_silence: f32,
+    /// 短于这个长度的语音段会被补零到这个长度，whisper 不接受不足 1s 的输入
+    pub min_speech: f32,
+    /// 长于这个长度的语音段会被强制切成 `slice` 长的小段
+    pub max_speech: f32,
+    pub slice: f32,
+}
+
+impl Default for VadSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.35,
+            min_silence: 0.2,
+            min_speech: 1.01,
+            max_speech: 60.0,
+            slice: 2.0,
+        }
+    }
+}
+
+impl VadSettings {
+    /// 各参数的取值范围，界面上的输入框也用这些范围
+    pub const THRESHOLD: RangeInclusive<f32> = 0.0..=1.0;
+    pub const MIN_SILENCE: RangeInclusive<f32> = 0.0..=5.0;
+    pub const MIN_SPEECH: RangeInclusive<f32> = 1.01..=30.0;
+    pub const MAX_SPEECH: RangeInclusive<f32> = 1.01..=600.0;
+    pub const SLICE: RangeInclusive<f32> = 0.5..=60.0;
+
+    /// 检查参数，slice 或 max_speech 为 0 时分段会卡死
+    pub fn validate(&self) -> Result<(), String> {
+        // 界面上的名字和配置文件里的键都给出来
+        let fields = [
+            ("vad_threshold", "threshold", self.threshold, Self::THRESHOLD),
+            ("vad_min_silence", "min_silence", self.min_silence, Self::MIN_SILENCE),
+            ("vad_min_speech", "min_speech", self.min_speech, Self::MIN_SPEECH),
+            ("vad_max_speech", "max_speech", self.max_speech, Self::MAX_SPEECH),
+            ("vad_slice", "slice", self.slice, Self::SLICE),
+        ];
+        for (label, key, value, range) in fields {
+            // NaN 不在任何范围里
+            if !range.contains(&value) {
+                return Err(trf(
+                    "vad_out_of_range",
+                    &[&tr(label), &key, range.start(), range.end(), &value],
+                ));
+            }
+        }
+        if self.min_speech > self.max_speech {
+            return Err(trf("vad_min_above_max", &[&self.min_speech, &self.max_speech]));
+        }
+        Ok(())
+    }
+}
+
+/// 语音分段状态机，只消费逐帧的语音概率，不依赖任何模型
+pub struct Segmenter {
+    sample_rate: u32,
+    chunk_size: usize,
+    threshold: f32,
+    silence_min_samples: usize,
+    min_samples: usize,
+    max_samples: usize,
+    slice_samples: usize,
+    /// 为 false 时只计算时间，不收集音频数据，用于界面上快速重新分段
+    collect_data: bool,
+    is_speech: bool,
+    start_time: f32,
+    speech_end_time: f32,
+    silence_samples: usize,
+    speech_samples: usize,
+    full_audio_chunk: Vec<f32>,
+    frame_index: usize,
+    /// 需要让使用者知道的警告，例如强制切分了过长的语音段
+    warnings: Vec<String>,
+}
+
+impl Segmenter {
+    pub fn new(sample_rate: u32, settings: VadSettings) -> Self {
+        Self {
+            sample_rate,
+            chunk_size: Self::seconds_to_samples(0.1, sample_rate),
+            threshold: settings.threshold,
+            silence_min_samples: Self::seconds_to_samples(settings.min_silence, sample_rate),
+            min_samples: Self::seconds_to_samples(settings.min_speech, sample_rate),
+            max_samples: Self::seconds_to_samples(settings.max_speech, sample_rate),
+            slice_samples: Self::seconds_to_samples(settings.slice, sample_rate).max(1),
+            collect_data: true,
+            is_speech: false,
+            start_time: 0.0,
+            speech_end_time: 0.0,
+            silence_samples: 0,
+            speech_samples: 0,
+            full_audio_chunk: Vec::new(),
+            frame_index: 0,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// 只计算语音段的时间，得到的 `ActiveSpeech` 没有 `data`
+    pub fn spans_only(sample_rate: u32, settings: VadSettings) -> Self {
+        Self {
+            collect_data: false,
+            ..Self::new(sample_rate, settings)
+        }
+    }
+
+    fn seconds_to_samples(seconds: f32, sample_rate: u32) -> usize {
+        (seconds * sample_rate as f32).round() as usize
+    }
+
+    /// 每一帧的采样数(100ms)
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// 取出上次调用以来产生的警告
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn append(&mut self, chunk: &[f32]) {
+        self.speech_samples += chunk.len();
+        if self.collect_data {
+            self.full_audio_chunk.extend_from_slice(chunk);
+        }
+    }
+
+    /// 输入一帧音频及其语音概率，返回在这一帧结束的语音段。
+    /// `prob` 为 NaN 表示检测器在这一帧出错，按语音处理，宁可多转录一段也不丢掉音频
+    pub fn push(&mut self, chunk: &[f32], prob: f32) -> Vec<ActiveSpeech> {
+        let sample_rate = self.sample_rate as f32;
+        let time = self.frame_index as f32 * self.chunk_size as f32 / sample_rate;
+        self.frame_index += 1;
+
+        if prob.is_nan() || prob > self.threshold {
+            self.silence_samples = 0;
+            self.append(chunk);
+            if !self.is_speech {
+                self.start_time = time;
+                self.is_speech = true;
+            }
+            self.speech_end_time = time + chunk.len() as f32 / sample_rate;
+            return Vec::new();
+        }
+        if !self.is_speech {
+            return Vec::new();
+        }
+        if self.silence_samples < self.silence_min_samples {
+            self.silence_samples += self.chunk_size;
+            self.append(chunk);
+            return Vec::new();
+        }
+        self.close(time)
+    }
+
+    /// 音频结束时调用，输出还没有结束的语音段
+    pub fn finish(&mut self) -> Vec<ActiveSpeech> {
+        if !self.is_speech {
+            return Vec::new();
+        }
+        let time = self.frame_index as f32 * self.chunk_size as f32 / self.sample_rate as f32;
+        self.close(time)
+    }
+
+    /// 结束当前的语音段，过长的切分、过短的补零。`time` 是结束的时刻，只用于警告
+    fn close(&mut self, time: f32) -> Vec<ActiveSpeech> {
+        let sample_rate = self.sample_rate as f32;
+        let mut active_speeches: Vec<ActiveSpeech> = Vec::new();
+        let start_time = self.start_time;
+        let speech_end_time = self.speech_end_time;
+        let len = self.speech_samples;
+        let duration = len as f32 / sample_rate;
+        if len > self.max_samples {
+            if self.collect_data {
+                let message = format!(
+                    "Found a {:.2}s chunks at {}s-{}s which is longer than {:.1}s.Forced slicing into {:.1}s pieces...",
+                    duration,
+                    start_time,
+                    time,
+                    self.max_samples as f32 / sample_rate,
+                    self.slice_samples as f32 / sample_rate
+                );
+                warn!("{}", message);
+                self.warnings.push(message);
+            }
+            let slice_duration = self.slice_samples as f32 / sample_rate;
+            for (idx, from) in (0..len).step_by(self.slice_samples).enumerate() {
+                let new_start_time = start_time + (idx as f32) * slice_duration;
+                if new_start_time >= speech_end_time {
+                    // 剩下的都是尾部静音
+                    break;
+                }
+                let to = (from + self.slice_samples).min(len);
+                let data_end_time = new_start_time + ((to - from) as f32) / sample_rate;
+                active_speeches.push(ActiveSpeech::new(
+                    new_start_time,
+                    data_end_time.min(speech_end_time),
+                    data_end_time,
+                    self.full_audio_chunk
+                        .get(from..to)
+                        .map(<[f32]>::to_vec)
+                        .unwrap_or_default(),
+                ));
+            }
+        } else if len < self.min_samples {
+            if self.collect_data {
+                warn!(
+                    "Found a {:.2}s chunks at {}s-{}s which is shorter than {:.2}s.Extending...",
+                    duration,
+                    start_time,
+                    time,
+                    self.min_samples as f32 / sample_rate
+                );
+                self.full_audio_chunk.resize(self.min_samples, 0.0);
+            }
+            active_speeches.push(ActiveSpeech::new(
+                start_time,
+                speech_end_time,
+                start_time + self.min_samples as f32 / sample_rate,
+                self.full_audio_chunk.clone(),
+            ));
+        } else {
+            active_speeches.push(ActiveSpeech::new(
+                start_time,
+                speech_end_time,
+                start_time + duration,
+                self.full_audio_chunk.clone(),
+            ));
+        }
+        self.is_speech = false;
+        self.silence_samples = 0;
+        self.speech_samples = 0;
+        self.full_audio_chunk.clear();
+        active_speeches
+    }
+}
+
+/// `do_vad` 的结果
+pub struct VadOutput {
+    pub speeches: Vec<ActiveSpeech>,
+    /// 每一帧的语音概率，检测器出错的帧为 NaN
+    pub probabilities: Vec<f32>,
+    /// 每一帧的采样数
+    pub chunk_size: usize,
+}
+
+pub fn do_vad<D: VoiceDetector>(
+    reporter: &mut Reporter<'_>,
     target_sample_rate: u32,
-    model_path: &str,
+    settings: VadSettings,
+    detector: &mut D,
     output_samples: &mut Vec<f32>,
-) -> Result<Vec<ActiveSpeech>, Box<dyn Error>> {
-    let mut vad = Vad::new(model_path, target_sample_rate.try_into().unwrap())?;
-
-    let mut is_speech = false;
-    let mut start_time = 0.0;
-    let mut full_audio_chunk: Vec<f32> = Vec::new();
-    let silence_min_samples = 3200;
-    let mut silence_samples = 0;
-    let sample_rate = target_sample_rate as f32;
-    let chunk_size = (0.1 * sample_rate) as usize;
-
-    // Add 1s of silence to the end of the samples
-    output_samples.extend(vec![0.0; sample_rate as usize]);
+) -> Result<VadOutput, Box<dyn Error>> {
+    let mut segmenter = Segmenter::new(target_sample_rate, settings);
+    let chunk_size = segmenter.chunk_size();
+
+    // Add 1s of silence to the end of the samples, and pad to whole frames so that
+    // the detector never sees a short chunk
+    let padded = output_samples.len() + target_sample_rate as usize;
+    output_samples.resize(padded.div_ceil(chunk_size) * chunk_size, 0.0);
     let chunks: Vec<_> = output_samples.chunks(chunk_size).enumerate().collect();
     let mut active_speeches: Vec<ActiveSpeech> = Vec::new();
-    let total = chunks.len();
+    let mut probabilities = Vec::with_capacity(chunks.len());
+    let total_seconds = output_samples.len() as f64 / target_sample_rate as f64;
+    let mut failed = 0;
 
+    for (i, chunk) in chunks.iter() {
+        reporter.set_seconds(
+            (*i * chunk_size) as f64 / target_sample_rate as f64,
+            total_seconds,
+        );
 
+        let prob = detector.compute(chunk).unwrap_or_else(|e| {
+            error!("VAD failed at frame {}: {}", i, e);
+            failed += 1;
+            f32::NAN
+        });
+        probabilities.push(prob);
+        active_speeches.extend(segmenter.push(chunk, prob));
+        for warning in segmenter.take_warnings() {
+            reporter.warn(warning);
+        }
+    }
+    active_speeches.extend(segmenter.finish());
+    if failed > 0 {
+        reporter.warn(format!(
+            "VAD failed on {} of {} frames, they were treated as speech",
+            failed,
+            chunks.len()
+        ));
+    }
 
-    for (i, chunk) in chunks.iter() {
-        {        *progress.lock().unwrap() = *i as f32 / total as f32;}
-
-        let time = *i as f32 * chunk_size as f32 / sample_rate;
-
-        match vad.compute(chunk) {
-            Ok(result) => {
-                let status = if result.prob > 0.35 {
-                    VadStatus::Speech
-                } else {
-                    VadStatus::Silence
-                };
-                match status {
-                    VadStatus::Speech => {
-                        silence_samples = 0;
-                        full_audio_chunk.extend_from_slice(chunk);
-                        if !is_speech {
-                            start_time = time;
-                            is_speech = true;
-                        }
-                    }
-                    VadStatus::Silence => {
-                        if is_speech {
-                            if silence_samples < silence_min_samples {
-                                silence_samples += chunk_size;
-                                full_audio_chunk.extend_from_slice(chunk);
-                                continue;
-                            }
-                            let len = full_audio_chunk.len();
-                            let duration = len as f32 / sample_rate;
-                            if duration > 60.0 {
-                                warn!(
-                                    "Found a {:.2}s chunks at {}s-{}s which is longer than 60.0s.Forced slicing into 2s pieces...",
-                                    duration, start_time, time
-                                );
-                                for (idx, slices) in full_audio_chunk.chunks(16000 * 2).enumerate()
-                                {
-                                    let new_start_time = start_time + (idx as f32) * 2.0;
-                                    active_speeches.push(ActiveSpeech::new(
-                                        new_start_time,
-                                        new_start_time + (slices.len() as f32) / sample_rate,
-                                        slices.to_vec(),
-                                    ));
-                                }
-                            } else if duration < 1.01 {
-                                warn!(
-                                    "Found a {:.2}s chunks at {}s-{}s which is shorter than 1.01s.Extending to 1.01s...",
-                                    duration, start_time, time
-                                );
-                                let padding_length = 16000 - len + 100;
-                                let padding = vec![0.0; padding_length]; // 动态创建一个 Vec
-                                full_audio_chunk.extend_from_slice(&padding); // 使用 extend_from_slice 扩展数据
-                                active_speeches.push(ActiveSpeech::new(
-                                    start_time,
-                                    time,
-                                    full_audio_chunk.clone(),
-                                ));
-                            } else {
-                                active_speeches.push(ActiveSpeech::new(
-                                    start_time,
-                                    time,
-                                    full_audio_chunk.clone(),
-                                ));
-                            }
-                            is_speech = false;
-                            silence_samples = 0;
-                            full_audio_chunk.clear();
-                        }
-                    }
-                    _ => {}
-                }
+    Ok(VadOutput {
+        speeches: active_speeches,
+        probabilities,
+        chunk_size,
+    })
+}
+
+/// 用已经算好的逐帧概率按新的参数重新分段，只返回时间
+pub fn resegment(
+    sample_rate: u32,
+    settings: VadSettings,
+    chunk_size: usize,
+    probabilities: &[f32],
+) -> Vec<ActiveSpeech> {
+    let mut segmenter = Segmenter::spans_only(sample_rate, settings);
+    let silence = vec![0.0; chunk_size];
+    let mut speeches = Vec::new();
+    for prob in probabilities {
+        speeches.extend(segmenter.push(&silence, *prob));
+    }
+    speeches.extend(segmenter.finish());
+    speeches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::JobEvent;
+    use std::sync::mpsc;
+
+    /// 按顺序返回给定的概率，`None` 表示这一帧出错，用完之后都是静音
+    struct ScriptedDetector {
+        probabilities: Vec<Option<f32>>,
+        next: usize,
+    }
+
+    impl ScriptedDetector {
+        fn new(probabilities: Vec<Option<f32>>) -> Self {
+            Self {
+                probabilities,
+                next: 0,
             }
-            Err(e) => {
-                if let ort::ErrorCode::InvalidArgument = e
-                    .downcast_ref::<ort::Error>()
-                    .ok_or("Error downcasting error.")?
-                    .code()
-                {
-                    warn!(
-                        "Got an InvalidArgument error from ort.This might be a normal behavior at the end of the audio."
-                    );
-                    is_speech = true;
-                } else {
-                    error!("Unknown error: {:?}", e);
-                }
+        }
+    }
+
+    impl VoiceDetector for ScriptedDetector {
+        fn compute(&mut self, _chunk: &[f32]) -> Result<f32, Box<dyn Error>> {
+            let prob = self.probabilities.get(self.next).copied().unwrap_or(Some(0.0));
+            self.next += 1;
+            prob.ok_or_else(|| "scripted failure".into())
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    /// 运行 do_vad，返回结果和发出的警告
+    fn run_vad<D: VoiceDetector>(
+        sample_rate: u32,
+        detector: &mut D,
+        mut samples: Vec<f32>,
+    ) -> (VadOutput, Vec<String>) {
+        let (sender, receiver) = mpsc::channel();
+        let mut reporter = Reporter::new(&sender);
+        let output = do_vad(
+            &mut reporter,
+            sample_rate,
+            VadSettings::default(),
+            detector,
+            &mut samples,
+        )
+        .unwrap();
+        let warnings = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                JobEvent::Warning(message) => Some(message),
+                _ => None,
+            })
+            .collect();
+        (output, warnings)
+    }
+
+    /// 1s 静音 + 2s 正弦波 + 2s 静音
+    fn tone_between_silence(sample_rate: u32) -> Vec<f32> {
+        let rate = sample_rate as usize;
+        let mut samples = vec![0.0; rate];
+        samples.extend((0..2 * rate).map(|i| {
+            0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin()
+        }));
+        samples.extend(vec![0.0; 2 * rate]);
+        samples
+    }
+
+    #[test]
+    fn energy_detector_finds_tone() {
+        let (output, warnings) =
+            run_vad(16000, &mut EnergyDetector::new(), tone_between_silence(16000));
+        assert!(warnings.is_empty());
+        assert_eq!(output.speeches.len(), 1);
+        assert_close(output.speeches[0].start_time, 1.0);
+        assert_close(output.speeches[0].end_time, 3.0);
+    }
+
+    #[test]
+    fn energy_detector_ignores_hiss_after_digital_silence() {
+        // 约 -55dB 的均匀噪声，只比 min_level_db 高一点
+        let mut seed = 1u32;
+        let mut noise = || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed as f32 / u32::MAX as f32 * 2.0 - 1.0) * 0.003
+        };
+        let mut samples = vec![0.0; 16000];
+        samples.extend((0..3 * 16000).map(|_| noise()));
+        let (output, _) = run_vad(16000, &mut EnergyDetector::new(), samples);
+        assert!(output.speeches.is_empty(), "{:?}", output.probabilities);
+    }
+
+    #[test]
+    fn failed_frames_are_kept_as_speech_and_reported() {
+        let mut detector = ScriptedDetector::new(vec![Some(0.0), Some(0.0), None, None]);
+        let (output, warnings) = run_vad(16000, &mut detector, vec![0.0; 16000]);
+        assert_eq!(output.speeches.len(), 1);
+        assert_close(output.speeches[0].start_time, 0.2);
+        assert_close(output.speeches[0].end_time, 0.4);
+        assert!(output.probabilities[2].is_nan() && output.probabilities[3].is_nan());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("2 of"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn samples_are_padded_to_whole_frames() {
+        let mut detector = ScriptedDetector::new(Vec::new());
+        let (output, _) = run_vad(16000, &mut detector, vec![0.0; 16000 + 123]);
+        // 2s 多一点，补齐后是 21 帧
+        assert_eq!(output.chunk_size, 1600);
+        assert_eq!(output.probabilities.len(), 21);
+    }
+
+    /// 把概率序列逐帧送进 16kHz 的分段器，返回 (开始, 结束, 数据结束) 时间
+    fn segment(settings: VadSettings, probabilities: &[f32]) -> Vec<(f32, f32, f32)> {
+        let mut segmenter = Segmenter::new(16000, settings);
+        let chunk = vec![0.0; segmenter.chunk_size()];
+        let mut speeches = Vec::new();
+        for prob in probabilities {
+            speeches.extend(segmenter.push(&chunk, *prob));
+        }
+        speeches.extend(segmenter.finish());
+        speeches
+            .iter()
+            .map(|speech| (speech.start_time, speech.end_time, speech.data_end_time))
+            .collect()
+    }
+
+    /// `count` 帧同样的概率
+    fn frames(count: usize, prob: f32) -> Vec<f32> {
+        vec![prob; count]
+    }
+
+    fn sequence(parts: &[(usize, f32)]) -> Vec<f32> {
+        parts.iter().flat_map(|(count, prob)| frames(*count, *prob)).collect()
+    }
+
+    #[test]
+    fn speech_starts_and_ends_at_threshold_crossings() {
+        let probs = sequence(&[(5, 0.1), (15, 0.9), (10, 0.1)]);
+        let spans = segment(VadSettings::default(), &probs);
+        assert_eq!(spans.len(), 1);
+        let (start, end, data_end) = spans[0];
+        assert_close(start, 0.5);
+        assert_close(end, 2.0);
+        // 数据里还包含了 0.2s 的尾部静音
+        assert_close(data_end, 2.2);
+    }
+
+    #[test]
+    fn probability_equal_to_threshold_is_silence() {
+        let settings = VadSettings::default();
+        let probs = sequence(&[(10, settings.threshold), (5, 0.1)]);
+        assert!(segment(settings, &probs).is_empty());
+    }
+
+    #[test]
+    fn gaps_shorter_than_min_silence_are_merged() {
+        // 0.2s 的停顿不到 min_silence，两段合成一段
+        let probs = sequence(&[(12, 0.9), (2, 0.1), (12, 0.9), (10, 0.1)]);
+        let spans = segment(VadSettings::default(), &probs);
+        assert_eq!(spans.len(), 1);
+        assert_close(spans[0].0, 0.0);
+        assert_close(spans[0].1, 2.6);
+
+        // 0.3s 的停顿足够分开
+        let probs = sequence(&[(12, 0.9), (3, 0.1), (12, 0.9), (10, 0.1)]);
+        let spans = segment(VadSettings::default(), &probs);
+        assert_eq!(spans.len(), 2);
+        assert_close(spans[0].0, 0.0);
+        assert_close(spans[0].1, 1.2);
+        assert_close(spans[1].0, 1.5);
+        assert_close(spans[1].1, 2.7);
+    }
+
+    #[test]
+    fn short_speech_is_padded_to_min_speech() {
+        let settings = VadSettings::default();
+        let mut segmenter = Segmenter::new(16000, settings);
+        let chunk = vec![0.5; segmenter.chunk_size()];
+        let mut speeches = Vec::new();
+        for prob in sequence(&[(3, 0.9), (10, 0.1)]) {
+            speeches.extend(segmenter.push(&chunk, prob));
+        }
+        assert_eq!(speeches.len(), 1);
+        assert_close(speeches[0].start_time, 0.0);
+        assert_close(speeches[0].end_time, 0.3);
+        assert_close(speeches[0].data_end_time, settings.min_speech);
+        assert_eq!(speeches[0].data.len(), 16160);
+        assert!(speeches[0].data[8000..].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn long_speech_is_split_into_slices() {
+        let settings = VadSettings {
+            max_speech: 1.0,
+            slice: 0.5,
+            ..VadSettings::default()
+        };
+        let mut segmenter = Segmenter::new(16000, settings);
+        let chunk = vec![0.5; segmenter.chunk_size()];
+        let mut speeches = Vec::new();
+        for prob in sequence(&[(25, 0.9), (10, 0.1)]) {
+            speeches.extend(segmenter.push(&chunk, prob));
+        }
+        let starts: Vec<f32> = speeches.iter().map(|speech| speech.start_time).collect();
+        assert_eq!(starts.len(), 5);
+        for (start, expected) in starts.iter().zip([0.0, 0.5, 1.0, 1.5, 2.0]) {
+            assert_close(*start, expected);
+        }
+        assert_close(speeches[4].end_time, 2.5);
+        assert!(speeches.iter().all(|speech| speech.data.len() == 8000));
+        assert_eq!(segmenter.take_warnings().len(), 1);
+    }
+
+    #[test]
+    fn finish_closes_open_speech() {
+        let probs = sequence(&[(2, 0.1), (5, 0.9)]);
+        let spans = segment(VadSettings::default(), &probs);
+        assert_eq!(spans.len(), 1);
+        assert_close(spans[0].0, 0.2);
+        assert_close(spans[0].1, 0.7);
+    }
+
+    #[test]
+    fn nan_probability_counts_as_speech() {
+        let probs = [0.1, f32::NAN, f32::NAN, 0.1, 0.1, 0.1, 0.1];
+        let spans = segment(VadSettings::default(), &probs);
+        assert_eq!(spans.len(), 1);
+        assert_close(spans[0].0, 0.1);
+        assert_close(spans[0].1, 0.3);
+    }
+
+    #[test]
+    fn resegment_matches_segmenter() {
+        let settings = VadSettings {
+            max_speech: 1.0,
+            slice: 0.5,
+            ..VadSettings::default()
+        };
+        let probs = sequence(&[(3, 0.1), (4, 0.8), (2, 0.2), (20, 0.7), (5, 0.0), (2, 0.9)]);
+        let expected = segment(settings, &probs);
+        let actual: Vec<(f32, f32, f32)> = resegment(16000, settings, 1600, &probs)
+            .iter()
+            .map(|speech| (speech.start_time, speech.end_time, speech.data_end_time))
+            .collect();
+        assert_eq!(actual, expected);
+        assert!(
+            resegment(16000, settings, 1600, &probs)
+                .iter()
+                .all(|speech| speech.data.is_empty())
+        );
+    }
+
+    #[test]
+    fn chunk_size_is_100ms_at_both_rates() {
+        assert_eq!(Segmenter::new(8000, VadSettings::default()).chunk_size(), 800);
+        assert_eq!(Segmenter::new(16000, VadSettings::default()).chunk_size(), 1600);
+    }
+
+    #[test]
+    fn both_rates_give_the_same_timestamps() {
+        let settings = VadSettings {
+            max_speech: 1.0,
+            slice: 0.5,
+            ..VadSettings::default()
+        };
+        let probs = sequence(&[(4, 0.1), (6, 0.9), (3, 0.1), (18, 0.9), (10, 0.1)]);
+        let spans_at = |sample_rate: u32| {
+            let mut segmenter = Segmenter::new(sample_rate, settings);
+            let chunk = vec![0.5; segmenter.chunk_size()];
+            let mut speeches = Vec::new();
+            for prob in &probs {
+                speeches.extend(segmenter.push(&chunk, *prob));
             }
+            speeches
+        };
+        let low = spans_at(8000);
+        let high = spans_at(16000);
+        assert!(!low.is_empty());
+        assert_eq!(low.len(), high.len());
+        for (low, high) in low.iter().zip(&high) {
+            assert_close(low.start_time, high.start_time);
+            assert_close(low.end_time, high.end_time);
+            assert_close(low.data_end_time, high.data_end_time);
+            assert_eq!(low.data.len() * 2, high.data.len());
+        }
+        let resegmented = resegment(8000, settings, 800, &probs);
+        for (low, high) in resegmented.iter().zip(&high) {
+            assert_close(low.start_time, high.start_time);
+            assert_close(low.end_time, high.end_time);
+        }
+    }
+
+    #[test]
+    fn do_vad_timestamps_agree_at_both_rates() {
+        let (low, _) = run_vad(8000, &mut EnergyDetector::new(), tone_between_silence(8000));
+        let (high, _) = run_vad(16000, &mut EnergyDetector::new(), tone_between_silence(16000));
+        assert_eq!(low.chunk_size, 800);
+        assert_eq!(high.chunk_size, 1600);
+        assert_eq!(low.probabilities.len(), high.probabilities.len());
+        assert_eq!(low.speeches.len(), high.speeches.len());
+        for (low, high) in low.speeches.iter().zip(&high.speeches) {
+            assert_close(low.start_time, high.start_time);
+            assert_close(low.end_time, high.end_time);
         }
     }
 
-    Ok(active_speeches)
+    #[test]
+    fn silero_rejects_unsupported_rates() {
+        assert!(SileroDetector::new("missing.onnx", 16000, 44100).is_err());
+        assert!(SileroDetector::new("missing.onnx", 12000, 8000).is_err());
+    }
+
+    #[test]
+    fn default_settings_are_valid() {
+        assert_eq!(VadSettings::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let invalid = [
+            VadSettings { slice: 0.0, ..Default::default() },
+            VadSettings { max_speech: 0.0, ..Default::default() },
+            VadSettings { min_speech: 20.0, max_speech: 10.0, ..Default::default() },
+            VadSettings { threshold: f32::NAN, ..Default::default() },
+            VadSettings { min_silence: -1.0, ..Default::default() },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
 }